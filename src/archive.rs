@@ -0,0 +1,100 @@
+use crate::data::Error;
+use flate2::read::GzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read},
+    path::Path,
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Calls `handle` with the path and content of every regular file in the tar
+/// archive at `path`. The archive may be compressed using gzip, xz or zstd
+/// which is detected by its magic number
+pub fn read_archive<'a, F>(path: &Path, mut handle: F) -> Result<(), Error<'a>>
+where
+    F: FnMut(&str, &[u8]) -> Result<(), Error<'a>>,
+{
+    read_archive_entries(path, |entry, content| {
+        content.map_or(Ok(()), |content| handle(entry, content))
+    })
+}
+
+/// Like `read_archive` but symlinks are passed to `handle` as well. They have
+/// no content
+pub fn read_archive_entries<'a, F>(path: &Path, mut handle: F) -> Result<(), Error<'a>>
+where
+    F: FnMut(&str, Option<&[u8]>) -> Result<(), Error<'a>>,
+{
+    let reader = BufReader::new(File::open(path)?);
+    let mut archive = tar::Archive::new(decompress(&path.to_string_lossy(), reader)?);
+    let mut content = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        let entry_path = entry.path()?.to_string_lossy().into_owned();
+        if entry_type.is_symlink() {
+            handle(&entry_path, None)?;
+        }
+        if !entry_type.is_file() {
+            continue;
+        }
+        content.clear();
+        let _ = entry.read_to_end(&mut content)?;
+        handle(&entry_path, Some(&content))?;
+    }
+    Ok(())
+}
+
+/// Parses the `key = value` lines of the `.PKGINFO` file of a package archive.
+/// Keys like `depend` may occur multiple times
+pub fn parse_pkginfo(content: &str) -> HashMap<String, Vec<String>> {
+    let mut info: HashMap<String, Vec<String>> = HashMap::new();
+    for line in content.lines().filter(|line| !line.starts_with('#')) {
+        if let Some((key, value)) = line.split_once(" = ") {
+            info.entry(String::from(key))
+                .or_default()
+                .push(String::from(value));
+        }
+    }
+    info
+}
+
+/// Decompresses the content of the file called `name` which may be compressed
+/// like an archive
+pub fn read_compressed<'a>(name: &str, content: &[u8]) -> Result<Vec<u8>, Error<'a>> {
+    let mut decompressed = Vec::new();
+    let _ = decompress(name, Cursor::new(content.to_vec()))?.read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+fn decompress<'a, R>(name: &str, mut reader: R) -> Result<Box<dyn Read>, Error<'a>>
+where
+    R: BufRead + 'static,
+{
+    let archive_error = |message: String| Error::Archive(format!("{}: {}", name, message));
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(GzDecoder::new(reader)))
+    } else if magic.starts_with(XZ_MAGIC) {
+        let mut tar = Vec::new();
+        lzma_rs::xz_decompress(&mut reader, &mut tar)
+            .map_err(|error| archive_error(error.to_string()))?;
+        Ok(Box::new(Cursor::new(tar)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        let mut tar = Vec::new();
+        // the archive may consist of multiple frames
+        while !reader.fill_buf()?.is_empty() {
+            let _ = StreamingDecoder::new(&mut reader)
+                .map_err(|error| archive_error(error.to_string()))?
+                .read_to_end(&mut tar)?;
+        }
+        Ok(Box::new(Cursor::new(tar)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
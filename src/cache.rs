@@ -0,0 +1,447 @@
+use crate::{
+    data::{Error, MismatchedLibrary, MissingVersion},
+    elf::{Architecture, Class, ElfFile, VersionRequirement},
+    resolve::{Resolution, SearchSource, SearchedLibrary},
+};
+use json::{self, JsonValue};
+use std::{
+    collections::HashMap,
+    env, fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+const CACHE_DIRECTORY: &str = "aurebuildcheck";
+const CACHE_FILE: &str = "scan.json";
+/// Incremented whenever the layout of the cache file changes
+const CACHE_VERSION: u64 = 6;
+
+/// Identifies the content of a file or directory without reading it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileKey {
+    size: u64,
+    modified: i64,
+    modified_nanoseconds: i64,
+    inode: u64,
+}
+
+/// What a previous run learned about a file
+#[derive(Debug)]
+pub enum CachedFile {
+    /// the file is no elf file requiring libraries
+    NotElf,
+    /// the parsed elf file and the resolution of its libraries. The resolution is only
+    /// known if neither the system libraries nor the searched directories changed since
+    Elf(Arc<ElfFile>, Option<Resolution>),
+}
+
+/// Remembers the verification of every file across runs, so files which did not
+/// change since the last run are neither read nor resolved again. It is stored
+/// at `$XDG_CACHE_HOME/aurebuildcheck/scan.json`
+#[derive(Debug)]
+pub struct ScanCache {
+    path: PathBuf,
+    /// the state of everything the resolution of the libraries depends on
+    library_state: String,
+    entries: Mutex<HashMap<PathBuf, CacheEntry>>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    key: FileKey,
+    elf: Option<Arc<ElfFile>>,
+    result: Option<ScanResult>,
+}
+
+#[derive(Debug, Clone)]
+struct ScanResult {
+    resolution: Resolution,
+    /// the state of the directories of the resolution when it was cached
+    directory_keys: Vec<Option<FileKey>>,
+}
+
+impl FileKey {
+    /// Returns the key of `path` or `None` if it does not exist
+    pub fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            size: metadata.size(),
+            modified: metadata.mtime(),
+            modified_nanoseconds: metadata.mtime_nsec(),
+            inode: metadata.ino(),
+        })
+    }
+
+    fn to_json(self) -> JsonValue {
+        json::object! {
+            "size" => self.size,
+            "modified" => self.modified,
+            "modified_nanoseconds" => self.modified_nanoseconds,
+            "inode" => self.inode,
+        }
+    }
+
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        Some(Self {
+            size: value["size"].as_u64()?,
+            modified: value["modified"].as_i64()?,
+            modified_nanoseconds: value["modified_nanoseconds"].as_i64()?,
+            inode: value["inode"].as_u64()?,
+        })
+    }
+}
+
+impl ScanCache {
+    /// Reads the cache of previous runs. `state_paths` are the files and directories
+    /// the library search depends on and `options` the settings changing the result
+    /// of a verification. If either differs from the previous run only the parsed
+    /// elf files are reused
+    pub fn read(state_paths: &[PathBuf], options: &str) -> Option<Self> {
+        let path = cache_directory()?.join(CACHE_FILE);
+        let library_state = state_paths
+            .iter()
+            .map(|state_path| {
+                let key = FileKey::read(state_path).map_or(JsonValue::Null, FileKey::to_json);
+                format!("{}: {}", state_path.to_string_lossy(), key.dump())
+            })
+            .fold(String::from(options), |state, line| state + "\n" + &line);
+        let mut cache = Self {
+            path,
+            library_state,
+            entries: Mutex::new(HashMap::new()),
+        };
+        // a missing or broken cache is simply rebuilt
+        if let Some(entries) = fs::read_to_string(&cache.path)
+            .ok()
+            .and_then(|content| json::parse(&content).ok())
+            .and_then(|content| cache.parse(&content))
+        {
+            cache.entries = Mutex::new(entries);
+        }
+        Some(cache)
+    }
+
+    #[allow(clippy::indexing_slicing)]
+    fn parse(&self, content: &JsonValue) -> Option<HashMap<PathBuf, CacheEntry>> {
+        if content["version"].as_u64()? != CACHE_VERSION {
+            return None;
+        }
+        let results_valid = content["library_state"].as_str()? == self.library_state;
+        Some(
+            content["files"]
+                .entries()
+                .filter_map(|(path, entry)| {
+                    let elf = &entry["elf"];
+                    let result = &entry["result"];
+                    let entry = CacheEntry {
+                        key: FileKey::from_json(&entry["key"])?,
+                        elf: if elf.is_null() {
+                            None
+                        } else {
+                            Some(Arc::new(elf_from_json(elf)?))
+                        },
+                        result: if results_valid && !result.is_null() {
+                            result_from_json(result)
+                        } else {
+                            None
+                        },
+                    };
+                    Some((PathBuf::from(path), entry))
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns what is known about `path` if the file did not change since
+    pub fn get(&self, path: &Path, key: FileKey) -> Option<CachedFile> {
+        let (elf, result) = self
+            .entries
+            .lock()
+            .expect("Lock should not be poisoned")
+            .get(path)
+            .filter(|entry| entry.key == key)
+            .map(|entry| (entry.elf.clone(), entry.result.clone()))?;
+        let Some(elf) = elf else {
+            return Some(CachedFile::NotElf);
+        };
+        // the directories are checked without holding the lock
+        let resolution = result
+            .filter(|result| {
+                result
+                    .resolution
+                    .directories
+                    .iter()
+                    .zip(&result.directory_keys)
+                    .all(|(directory, key)| FileKey::read(directory) == *key)
+            })
+            .map(|result| result.resolution);
+        Some(CachedFile::Elf(elf, resolution))
+    }
+
+    /// Remembers the verification of `path`. `elf` is `None` for files which are
+    /// no elf files
+    pub fn insert(
+        &self,
+        path: &Path,
+        key: FileKey,
+        elf: Option<Arc<ElfFile>>,
+        resolution: Option<&Resolution>,
+    ) {
+        let result = resolution.map(|resolution| ScanResult {
+            resolution: resolution.clone(),
+            directory_keys: resolution
+                .directories
+                .iter()
+                .map(|directory| FileKey::read(directory))
+                .collect(),
+        });
+        let _ = self
+            .entries
+            .lock()
+            .expect("Lock should not be poisoned")
+            .insert(path.to_path_buf(), CacheEntry { key, elf, result });
+    }
+
+    /// Writes the cache for the next run. Files which no longer exist are dropped
+    #[allow(clippy::indexing_slicing)]
+    pub fn write<'a>(&self) -> Result<(), Error<'a>> {
+        let mut files = JsonValue::new_object();
+        for (path, entry) in self
+            .entries
+            .lock()
+            .expect("Lock should not be poisoned")
+            .iter()
+            .filter(|(path, _)| path.exists())
+        {
+            files[&*path.to_string_lossy()] = json::object! {
+                "key" => entry.key.to_json(),
+                "elf" => entry.elf.as_deref().map_or(JsonValue::Null, elf_to_json),
+                "result" => entry.result.as_ref().map_or(JsonValue::Null, result_to_json),
+            };
+        }
+        let content = json::object! {
+            "version" => CACHE_VERSION,
+            "library_state" => self.library_state.clone(),
+            "files" => files,
+        };
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        // replacing the file at once keeps the cache intact if the program is interrupted
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, content.dump())?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
+
+fn cache_directory() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|directory| directory.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|directory| directory.join(CACHE_DIRECTORY))
+}
+
+fn strings_from_json(value: &JsonValue) -> Option<Vec<String>> {
+    value
+        .members()
+        .map(|member| member.as_str().map(String::from))
+        .collect()
+}
+
+fn elf_to_json(elf: &ElfFile) -> JsonValue {
+    json::object! {
+        "class" => class_to_json(elf.architecture.class),
+        "machine" => elf.architecture.machine,
+        "executable" => elf.executable,
+        "needed" => elf.needed.clone(),
+        "rpath" => elf.rpath.clone(),
+        "runpath" => elf.runpath.clone(),
+        "version_requirements" => elf
+            .version_requirements
+            .iter()
+            .map(|requirement| {
+                json::object! {
+                    "library" => requirement.library.clone(),
+                    "versions" => requirement.versions.clone(),
+                }
+            })
+            .collect::<Vec<JsonValue>>(),
+        "version_definitions" => elf.version_definitions.clone(),
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+fn elf_from_json(value: &JsonValue) -> Option<ElfFile> {
+    Some(ElfFile {
+        architecture: architecture_from_json(value)?,
+        executable: value["executable"].as_bool()?,
+        needed: strings_from_json(&value["needed"])?,
+        rpath: strings_from_json(&value["rpath"])?,
+        runpath: strings_from_json(&value["runpath"])?,
+        version_requirements: value["version_requirements"]
+            .members()
+            .map(|requirement| {
+                Some(VersionRequirement {
+                    library: String::from(requirement["library"].as_str()?),
+                    versions: strings_from_json(&requirement["versions"])?,
+                })
+            })
+            .collect::<Option<Vec<VersionRequirement>>>()?,
+        version_definitions: strings_from_json(&value["version_definitions"])?,
+    })
+}
+
+const fn class_to_json(class: Class) -> u8 {
+    match class {
+        Class::Elf32 => 32,
+        Class::Elf64 => 64,
+    }
+}
+
+/// Reads the `class` and `machine` members of `value`
+#[allow(clippy::indexing_slicing)]
+fn architecture_from_json(value: &JsonValue) -> Option<Architecture> {
+    let class = match value["class"].as_u8()? {
+        32 => Class::Elf32,
+        64 => Class::Elf64,
+        _ => return None,
+    };
+    Some(Architecture {
+        class,
+        machine: value["machine"].as_u16()?,
+    })
+}
+
+fn result_to_json(result: &ScanResult) -> JsonValue {
+    let resolution = &result.resolution;
+    let missing_versions = resolution
+        .missing_versions
+        .iter()
+        .map(|missing| {
+            json::object! {
+                "library" => missing.library.clone(),
+                "version" => missing.version.clone(),
+            }
+        })
+        .collect::<Vec<JsonValue>>();
+    let mismatched = resolution
+        .mismatched
+        .iter()
+        .map(|mismatch| {
+            json::object! {
+                "library" => mismatch.library.clone(),
+                "path" => mismatch.path.clone(),
+                "class" => class_to_json(mismatch.architecture.class),
+                "machine" => mismatch.architecture.machine,
+            }
+        })
+        .collect::<Vec<JsonValue>>();
+    let searched = resolution
+        .searched
+        .iter()
+        .map(|searched| {
+            let (directory, origin) = match searched.source {
+                SearchSource::Environment => (None, false),
+                SearchSource::SearchPath {
+                    ref directory,
+                    origin,
+                } => (Some(directory.clone()), origin),
+            };
+            json::object! {
+                "library" => searched.library.clone(),
+                "path" => searched.path.to_string_lossy().into_owned(),
+                "directory" => directory,
+                "origin" => origin,
+            }
+        })
+        .collect::<Vec<JsonValue>>();
+    let directories = resolution
+        .directories
+        .iter()
+        .zip(&result.directory_keys)
+        .map(|(directory, key)| {
+            json::object! {
+                "path" => directory.to_string_lossy().into_owned(),
+                "key" => key.map_or(JsonValue::Null, FileKey::to_json),
+            }
+        })
+        .collect::<Vec<JsonValue>>();
+    json::object! {
+        "missing" => resolution.missing.clone(),
+        "missing_versions" => missing_versions,
+        "libraries" => resolution
+            .libraries
+            .iter()
+            .map(|library| library.to_string_lossy().into_owned())
+            .collect::<Vec<String>>(),
+        "mismatched" => mismatched,
+        "searched" => searched,
+        "directories" => directories,
+    }
+}
+
+#[allow(clippy::indexing_slicing)]
+fn result_from_json(value: &JsonValue) -> Option<ScanResult> {
+    let mut directories = Vec::new();
+    let mut directory_keys = Vec::new();
+    for directory in value["directories"].members() {
+        directories.push(PathBuf::from(directory["path"].as_str()?));
+        let key = &directory["key"];
+        directory_keys.push(if key.is_null() {
+            None
+        } else {
+            Some(FileKey::from_json(key)?)
+        });
+    }
+    Some(ScanResult {
+        resolution: Resolution {
+            missing: strings_from_json(&value["missing"])?,
+            missing_versions: value["missing_versions"]
+                .members()
+                .map(|missing| {
+                    Some(MissingVersion {
+                        library: String::from(missing["library"].as_str()?),
+                        version: String::from(missing["version"].as_str()?),
+                    })
+                })
+                .collect::<Option<Vec<MissingVersion>>>()?,
+            mismatched: value["mismatched"]
+                .members()
+                .map(|mismatch| {
+                    Some(MismatchedLibrary {
+                        library: String::from(mismatch["library"].as_str()?),
+                        path: String::from(mismatch["path"].as_str()?),
+                        architecture: architecture_from_json(mismatch)?,
+                    })
+                })
+                .collect::<Option<Vec<MismatchedLibrary>>>()?,
+            searched: value["searched"]
+                .members()
+                .map(|searched| {
+                    let directory = &searched["directory"];
+                    Some(SearchedLibrary {
+                        library: String::from(searched["library"].as_str()?),
+                        path: PathBuf::from(searched["path"].as_str()?),
+                        source: if directory.is_null() {
+                            SearchSource::Environment
+                        } else {
+                            SearchSource::SearchPath {
+                                directory: String::from(directory.as_str()?),
+                                origin: searched["origin"].as_bool()?,
+                            }
+                        },
+                    })
+                })
+                .collect::<Option<Vec<SearchedLibrary>>>()?,
+            libraries: strings_from_json(&value["libraries"])?
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            directories,
+        },
+        directory_keys,
+    })
+}
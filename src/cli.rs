@@ -1,16 +1,10 @@
-use crate::data::Error;
+use crate::{data::Error, root};
 use clap::{
     app_from_crate, crate_authors, crate_description, crate_name, crate_version, AppSettings, Arg,
     ArgMatches, SubCommand,
 };
 use regex::RegexSet;
-
-/// Specifies the various ways to check elf files for missing libraries
-#[derive(Debug)]
-pub enum Command {
-    Ldd,
-    Readelf,
-}
+use std::{fs, path::PathBuf};
 
 // Specifies the various ways to output the missing library information
 #[derive(Debug)]
@@ -22,9 +16,13 @@ pub enum Output {
 /// These Settings define how the program operates and are used everywhere
 #[derive(Debug)]
 pub struct CommandLineSettings {
-    pub command: Command,
     pub packages: Vec<String>,
+    /// directories and files checked instead of packages
+    pub paths: Vec<PathBuf>,
+    /// package archives checked instead of installed packages
+    pub archives: Vec<PathBuf>,
     pub all_packages: bool,
+    pub include_outdated: bool,
     pub ignore_libraries: Vec<String>,
     pub ignore_libraries_regex: Option<RegexSet>,
     pub show_candidates: bool,
@@ -33,14 +31,29 @@ pub struct CommandLineSettings {
     pub group_by_file: bool,
     pub group_by_library: bool,
     pub group_by_containing_package: bool,
+    pub db_path: PathBuf,
+    /// the system which is checked, `/` unless a mounted image is checked
+    pub root: PathBuf,
+    pub no_cache: bool,
+    pub deep: bool,
+    /// reports unused libraries and symbols of indirectly loaded libraries
+    pub linking: bool,
+    pub plugins: bool,
+    /// searches the `LD_LIBRARY_PATH` of the current environment
+    pub use_environment: bool,
+    pub fragile: bool,
+    /// reports libraries of packages which are not declared as dependencies
+    pub undeclared: bool,
 }
 
 impl Default for CommandLineSettings {
     fn default() -> Self {
         Self {
-            command: Command::Ldd,
             packages: vec![],
+            paths: vec![],
+            archives: vec![],
             all_packages: false,
+            include_outdated: false,
             ignore_libraries: vec![],
             ignore_libraries_regex: None,
             show_candidates: false,
@@ -49,6 +62,15 @@ impl Default for CommandLineSettings {
             group_by_file: false,
             group_by_library: false,
             group_by_containing_package: false,
+            db_path: PathBuf::from("/var/lib/pacman"),
+            root: PathBuf::from("/"),
+            no_cache: false,
+            deep: false,
+            linking: false,
+            plugins: false,
+            use_environment: false,
+            fragile: false,
+            undeclared: false,
         }
     }
 }
@@ -58,11 +80,6 @@ pub fn get_command_line_settings<'a>() -> Result<CommandLineSettings, Error<'a>>
     let parser = setup_command_line_parser();
 
     if let Some(subcommand) = parser.subcommand_matches("ldd") {
-        settings.command = Command::Ldd;
-        get_subcommand_line_settings(subcommand, &mut settings)?;
-    }
-    if let Some(subcommand) = parser.subcommand_matches("readelf") {
-        settings.command = Command::Readelf;
         get_subcommand_line_settings(subcommand, &mut settings)?;
     }
 
@@ -84,6 +101,32 @@ pub fn get_command_line_settings<'a>() -> Result<CommandLineSettings, Error<'a>>
     if parser.is_present("group by containing package") {
         settings.group_by_containing_package = true;
     }
+    if let Some(root) = parser.value_of_os("root") {
+        // files below the root are canonicalized, so the root has to be as well
+        settings.root = fs::canonicalize(root)
+            .map_err(|_| Error::PathNotFound(root.to_string_lossy().into_owned()))?;
+    }
+    // like pacman the database is located inside of the root unless it is given
+    if let Some(db_path) = parser.value_of_os("database path") {
+        settings.db_path = PathBuf::from(db_path);
+    } else {
+        settings.db_path = root::join(&settings.root, &settings.db_path);
+    }
+    if parser.is_present("no cache") {
+        settings.no_cache = true;
+    }
+    if parser.is_present("plugins") {
+        settings.plugins = true;
+    }
+    if parser.is_present("use environment") {
+        settings.use_environment = true;
+    }
+    if parser.is_present("fragile") {
+        settings.fragile = true;
+    }
+    if parser.is_present("undeclared") {
+        settings.undeclared = true;
+    }
 
     // by default (if not specified otherwise) only files
     // and libraries are printed. Packages are printed only
@@ -107,9 +150,24 @@ fn get_subcommand_line_settings<'a>(
         settings.packages = packages;
         settings.packages.sort();
     }
+    if let Some(paths) = parser.values_of_os("paths") {
+        settings.paths = paths.map(PathBuf::from).collect();
+    }
+    if let Some(archives) = parser.values_of_os("archives") {
+        settings.archives = archives.map(PathBuf::from).collect();
+    }
     if parser.is_present("all packages") {
         settings.all_packages = true;
     }
+    if parser.is_present("include outdated") {
+        settings.include_outdated = true;
+    }
+    if parser.is_present("deep") {
+        settings.deep = true;
+    }
+    if parser.is_present("linking") {
+        settings.linking = true;
+    }
     if let Some(ignore_libraries) = parser.values_of_lossy("ignore libraries") {
         settings.ignore_libraries = ignore_libraries;
         if !settings.quite {
@@ -143,60 +201,46 @@ fn setup_command_line_parser<'a>() -> ArgMatches<'a> {
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .subcommand(
             SubCommand::with_name("ldd")
-                .about("Checks packages using ldd")
+                .visible_alias("readelf")
+                .about("Checks packages and the libraries they load like ldd")
                 .arg(
                     Arg::with_name("packages")
                         .multiple(true)
                         .use_delimiter(true)
                         .number_of_values(1)
                         .help("List of packages to check (eg package1,package2)")
-                        .required_unless("all_packages")
-                        .conflicts_with("all_packages"),
-                )
-                .arg(
-                    Arg::with_name("all packages")
-                        .short("a")
-                        .long("all_packages")
-                        .help("Checks all installed packages marked as local")
-                        .conflicts_with("packages"),
+                        .required_unless_one(&["all packages", "paths", "archives"])
+                        .conflicts_with("all packages"),
                 )
                 .arg(
-                    Arg::with_name("ignore libraries")
-                        .short("i")
-                        .long("ignore_libs")
+                    Arg::with_name("paths")
+                        .long("paths")
                         .multiple(true)
                         .use_delimiter(true)
                         .number_of_values(1)
-                        .help("List of libraries to ignore (eg lib1,lib2)"),
+                        .value_name("path")
+                        .help("Checks the files below the paths which no package owns (eg /usr/local,/opt)")
+                        .long_help(
+                            "Checks the files below the paths which no package owns (eg /usr/local,/opt).
+Directories are searched recursively. The files of every path
+are reported like a package named after the path",
+                        )
+                        .conflicts_with_all(&["packages", "all packages", "archives"]),
                 )
                 .arg(
-                    Arg::with_name("ignore libraries via regex")
-                        .short("r")
-                        .long("ignore_libs_regex")
+                    Arg::with_name("archives")
+                        .long("archives")
                         .multiple(true)
                         .use_delimiter(true)
                         .number_of_values(1)
-                        .help("List of libraries to ignore (eg lib1,lib2) via regex")
+                        .value_name("archive")
+                        .help("Checks package archives before they are installed (eg foo.pkg.tar.zst)")
                         .long_help(
-                            "
-List of libraries to ignore (eg lib1,lib2) via regex.
-More information about how to define regex at
-https://docs.rs/regex/#syntax
-                        ",
-                        ),
-                ),
-        )
-        .subcommand(
-            SubCommand::with_name("readelf")
-                .about("Checks packages using readelf")
-                .arg(
-                    Arg::with_name("packages")
-                        .multiple(true)
-                        .use_delimiter(true)
-                        .number_of_values(1)
-                        .help("List of packages to check (eg package1,package2)")
-                        .required_unless("all_packages")
-                        .conflicts_with("all_packages"),
+                            "Checks package archives before they are installed (eg foo.pkg.tar.zst).
+The files are checked as if they were installed. Libraries
+shipped in the archive are not reported as missing",
+                        )
+                        .conflicts_with_all(&["packages", "all packages", "paths"]),
                 )
                 .arg(
                     Arg::with_name("all packages")
@@ -205,6 +249,36 @@ https://docs.rs/regex/#syntax
                         .help("Checks all installed packages marked as local")
                         .conflicts_with("packages"),
                 )
+                .arg(
+                    Arg::with_name("include outdated")
+                        .long("include_outdated")
+                        .requires("all packages")
+                        .help("Also checks repository packages whose installed version differs"),
+                )
+                .arg(
+                    Arg::with_name("deep")
+                        .long("deep")
+                        .help("Also checks for undefined symbols no library defines like ldd -r")
+                        .long_help(
+                            "Also checks for undefined symbols no library defines like ldd -r.
+Only files whose libraries are all found are checked. Shared
+objects outside the linker path are skipped as the programs
+loading them define some of their symbols. Reading the symbol
+tables of every library makes the check slower",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("linking")
+                        .long("linking")
+                        .help("Also reports overlinked and underlinked files")
+                        .long_help(
+                            "Also reports overlinked and underlinked files. Overlinked
+files require libraries they use no symbol of, underlinked
+files use symbols of libraries which are only loaded as
+libraries of their libraries. Only files whose libraries are
+all found are checked",
+                        ),
+                )
                 .arg(
                     Arg::with_name("ignore libraries")
                         .short("i")
@@ -238,10 +312,10 @@ https://docs.rs/regex/#syntax
                 .help("Prints a list of packages containing the missing library")
                 .long_help(
                     "Prints a list of packages containing the missing library.
-The listed packages may or may not add the library to the
-system path. Therefore just because a package is listed
-doesn't mean it will satisfy the library requirement.
-Requires pkgfile",
+Packages placing the library outside of the directories
+searched by the dynamic linker or providing it for another
+architecture are marked as such and listed last.
+Requires the pacman files databases (pacman -Fy)",
                 ),
         )
         .arg(
@@ -274,5 +348,80 @@ Requires pkgfile",
                 .long("group_by_containing_package")
                 .help("groups output by packages containing libraries"),
         )
+        .arg(
+            Arg::with_name("root")
+                .long("root")
+                .visible_alias("sysroot")
+                .takes_value(true)
+                .value_name("path")
+                .help("Checks the system installed below path instead of the running one")
+                .long_help(
+                    "Checks the system installed below path instead of the running one,
+like a container or a chroot. Packages, their files and the
+libraries they load are searched inside of path. The
+LD_LIBRARY_PATH of the running system is ignored",
+                ),
+        )
+        .arg(
+            Arg::with_name("database path")
+                .short("b")
+                .long("dbpath")
+                .takes_value(true)
+                .value_name("path")
+                .help("Location of the pacman database (default: /var/lib/pacman)"),
+        )
+        .arg(
+            Arg::with_name("no cache")
+                .long("no_cache")
+                .help("Verifies every file instead of reusing the results of previous runs")
+                .long_help(
+                    "Verifies every file instead of reusing the results of previous runs.
+Results are stored in $XDG_CACHE_HOME/aurebuildcheck and reused
+as long as neither the file nor the system libraries changed",
+                ),
+        )
+        .arg(
+            Arg::with_name("plugins")
+                .long("plugins")
+                .help("Reports shared objects outside the linker path separately as plugins")
+                .long_help(
+                    "Reports shared objects outside the linker path separately as plugins.
+Plugins are loaded with dlopen by the programs of the package,
+so their libraries are searched in the RPATH and RUNPATH of
+these programs as well",
+                ),
+        )
+        .arg(
+            Arg::with_name("use environment")
+                .long("use_environment")
+                .help("Searches libraries in the LD_LIBRARY_PATH of the current environment")
+                .long_help(
+                    "Searches libraries in the LD_LIBRARY_PATH of the current environment.
+By default it is ignored, as the libraries of a development
+environment hide libraries missing on the system",
+                ),
+        )
+        .arg(
+            Arg::with_name("fragile")
+                .long("fragile")
+                .help("Reports libraries which are only found outside of the linker path")
+                .long_help(
+                    "Reports libraries which are only found outside of the linker path:
+in the LD_LIBRARY_PATH (with --use_environment), in an RPATH
+or RUNPATH pointing to a build directory or relative to
+$ORIGIN pointing outside of the package",
+                ),
+        )
+        .arg(
+            Arg::with_name("undeclared")
+                .long("undeclared")
+                .help("Reports libraries of packages which are not declared as dependencies")
+                .long_help(
+                    "Reports libraries of packages which are not declared as dependencies.
+The package owning each library is compared with the depends
+of the checked package and their depends, which may be
+satisfied by provides as well. Files of --paths are not checked",
+                ),
+        )
         .get_matches()
 }
@@ -1,3 +1,7 @@
+use crate::{
+    database::{InstallReason, InstalledLibrary, PackageFile},
+    elf::Architecture,
+};
 use regex;
 use std::{collections::HashSet, error, fmt, io, iter::FromIterator, rc::Rc};
 
@@ -5,8 +9,11 @@ use std::{collections::HashSet, error, fmt, io, iter::FromIterator, rc::Rc};
 pub enum Error<'a> {
     Dependency(&'a str),
     ExecutionIO(io::Error),
-    Execution(String),
     Regex(regex::Error),
+    PackageNotFound(String),
+    PathNotFound(String),
+    Database(String),
+    Archive(String),
 }
 
 impl fmt::Display for Error<'_> {
@@ -14,8 +21,11 @@ impl fmt::Display for Error<'_> {
         match *self {
             Error::Dependency(dep) => write!(f, "Dependency missing: {}", dep),
             Error::ExecutionIO(ref err) => write!(f, "Command ExecutionIO error: {}", err),
-            Error::Execution(ref err) => write!(f, "Command ExecutionIO error: {}", err),
             Error::Regex(ref err) => write!(f, "Regex Error: {}", err),
+            Error::PackageNotFound(ref package) => write!(f, "Package not found: {}", package),
+            Error::PathNotFound(ref path) => write!(f, "Path not found: {}", path),
+            Error::Database(ref entry) => write!(f, "Invalid database entry: {}", entry),
+            Error::Archive(ref err) => write!(f, "Invalid archive: {}", err),
         }
     }
 }
@@ -23,7 +33,11 @@ impl fmt::Display for Error<'_> {
 impl error::Error for Error<'_> {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
-            Error::Dependency(_) | Error::Execution(_) => None,
+            Error::Dependency(_)
+            | Error::PackageNotFound(_)
+            | Error::PathNotFound(_)
+            | Error::Database(_)
+            | Error::Archive(_) => None,
             Error::ExecutionIO(ref err) => Some(err),
             Error::Regex(ref err) => Some(err),
         }
@@ -45,60 +59,234 @@ impl From<regex::Error> for Error<'_> {
 #[derive(Debug)]
 pub struct ProcessingPackage {
     pub name: String,
+    pub version: Option<String>,
+    pub install_reason: Option<InstallReason>,
+    pub validation: Vec<String>,
     pub file_dependencies: Vec<ProcessingFileDependency>,
+    pub runtime_dependencies: Vec<RuntimeDependency>,
 }
 
 impl ProcessingPackage {
     pub fn new<S: Into<String>>(name: S) -> Self {
         Self {
             name: name.into(),
+            version: None,
+            install_reason: None,
+            validation: vec![],
             file_dependencies: vec![],
+            runtime_dependencies: vec![],
         }
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ProcessingFileDependency {
     pub file_name: String,
+    pub architecture: Architecture,
+    /// shared object outside the linker path, loaded by a program of the package
+    pub plugin: bool,
     pub library_dependencies: HashSet<String>,
+    pub version_dependencies: HashSet<MissingVersion>,
+    pub symbol_dependencies: HashSet<String>,
+    pub architecture_mismatches: HashSet<MismatchedLibrary>,
+    pub fragile_dependencies: HashSet<FragileLibrary>,
+    pub search_path_issues: Vec<SearchPathIssue>,
+    /// direct libraries defining none of the undefined symbols
+    pub unused_libraries: HashSet<String>,
+    pub underlinked_symbols: HashSet<UnderlinkedSymbol>,
+    pub undeclared_dependencies: HashSet<UndeclaredDependency>,
+}
+
+/// A symbol version required from a library which the loaded library does not define
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MissingVersion {
+    pub library: String,
+    pub version: String,
+}
+
+/// A missing library which only exists for another architecture, like the
+/// 64-bit library while a 32-bit file requires it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MismatchedLibrary {
+    pub library: String,
+    pub path: String,
+    pub architecture: Architecture,
+}
+
+/// A library which is only found through the current environment or a
+/// directory which is not available on every system
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FragileLibrary {
+    pub library: String,
+    pub path: String,
+    pub reason: Fragility,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Fragility {
+    /// found in `LD_LIBRARY_PATH`
+    Environment,
+    /// found in an `DT_RPATH` or `DT_RUNPATH` pointing to a build directory
+    BuildDirectory(String),
+    /// found relative to `$ORIGIN` outside of the package
+    OriginOutsidePackage(String),
+}
+
+impl fmt::Display for Fragility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Environment => write!(f, "LD_LIBRARY_PATH"),
+            Self::BuildDirectory(ref directory) => {
+                write!(f, "build directory {}", directory)
+            }
+            Self::OriginOutsidePackage(ref directory) => {
+                write!(f, "$ORIGIN outside of the package {}", directory)
+            }
+        }
+    }
+}
+
+/// A symbol only defined by a library which is not a direct dependency
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UnderlinkedSymbol {
+    pub symbol: String,
+    pub library: String,
+}
+
+/// A library of a package which is not declared as a dependency
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UndeclaredDependency {
+    pub library: String,
+    pub path: String,
+    pub package: String,
+}
+
+/// A `DT_RPATH` or `DT_RUNPATH` entry which might not work on other systems
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchPathIssue {
+    /// `RPATH` or `RUNPATH`
+    pub tag: &'static str,
+    pub entry: String,
+    pub problem: SearchPathProblem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchPathProblem {
+    /// an absolute directory which is not part of `/usr`
+    OutsideUsr,
+    /// relative to the working directory instead of `$ORIGIN`
+    Relative,
+    NotFound,
+}
+
+impl fmt::Display for SearchPathProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::OutsideUsr => write!(f, "points outside of /usr"),
+            Self::Relative => write!(f, "is relative without $ORIGIN"),
+            Self::NotFound => write!(f, "does not exist"),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Package {
     pub name: String,
+    pub version: Option<String>,
+    pub install_reason: Option<InstallReason>,
+    pub validation: Vec<String>,
     pub file_dependencies: Vec<FileDependency>,
     pub library_requirements: Vec<LibraryRequired>,
+    pub haskell_requirements: Vec<HaskellRequired>,
+    pub version_requirements: Vec<VersionRequired>,
+    pub symbol_requirements: Vec<SymbolRequired>,
     pub packages_containing: Vec<PackagesContaining>,
+    pub library_replacements: Vec<LibraryReplacement>,
+    pub runtime_dependencies: Vec<RuntimeDependency>,
+}
+
+impl Package {
+    /// A package with any file or runtime finding needs to be rebuilt
+    pub fn has_issues(&self) -> bool {
+        self.file_dependencies
+            .iter()
+            .any(FileDependency::has_issues)
+            || !self.runtime_dependencies.is_empty()
+    }
 }
 
 impl From<ProcessingPackage> for Package {
     fn from(package: ProcessingPackage) -> Self {
         Self {
             name: package.name,
+            version: package.version,
+            install_reason: package.install_reason,
+            validation: package.validation,
             file_dependencies: package
                 .file_dependencies
                 .into_iter()
                 .map(|dependency| dependency.into())
                 .collect(),
             library_requirements: vec![],
+            haskell_requirements: vec![],
+            version_requirements: vec![],
+            symbol_requirements: vec![],
             packages_containing: vec![],
+            library_replacements: vec![],
+            runtime_dependencies: package.runtime_dependencies,
         }
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct FileDependency {
     pub file_name: Rc<String>,
+    pub architecture: Architecture,
+    pub plugin: bool,
     pub library_dependencies: HashSet<Rc<String>>,
+    pub version_dependencies: HashSet<MissingVersion>,
+    pub symbol_dependencies: HashSet<Rc<String>>,
+    pub architecture_mismatches: HashSet<MismatchedLibrary>,
+    pub fragile_dependencies: HashSet<FragileLibrary>,
+    pub search_path_issues: Vec<SearchPathIssue>,
+    /// direct libraries defining none of the undefined symbols
+    pub unused_libraries: HashSet<String>,
+    pub underlinked_symbols: HashSet<UnderlinkedSymbol>,
+    pub undeclared_dependencies: HashSet<UndeclaredDependency>,
+}
+
+impl FileDependency {
+    /// A file with any finding apart from the lints needs to be rebuilt
+    pub fn has_issues(&self) -> bool {
+        !self.library_dependencies.is_empty()
+            || !self.architecture_mismatches.is_empty()
+            || !self.version_dependencies.is_empty()
+            || !self.symbol_dependencies.is_empty()
+            || !self.fragile_dependencies.is_empty()
+    }
 }
 
 impl From<ProcessingFileDependency> for FileDependency {
     fn from(dependency: ProcessingFileDependency) -> Self {
         Self {
             file_name: Rc::new(dependency.file_name),
+            architecture: dependency.architecture,
+            plugin: dependency.plugin,
             library_dependencies: HashSet::from_iter(
                 dependency.library_dependencies.into_iter().map(Rc::new),
             ),
+            version_dependencies: dependency.version_dependencies,
+            symbol_dependencies: dependency
+                .symbol_dependencies
+                .into_iter()
+                .map(Rc::new)
+                .collect(),
+            architecture_mismatches: dependency.architecture_mismatches,
+            fragile_dependencies: dependency.fragile_dependencies,
+            search_path_issues: dependency.search_path_issues,
+            unused_libraries: dependency.unused_libraries,
+            underlinked_symbols: dependency.underlinked_symbols,
+            undeclared_dependencies: dependency.undeclared_dependencies,
         }
     }
 }
@@ -109,8 +297,64 @@ pub struct LibraryRequired {
     pub files_requiring: Vec<Rc<String>>,
 }
 
+/// Missing haskell libraries built by the same ghc version. They are reported
+/// together since every library needs to be rebuilt with the installed ghc
+#[derive(Debug)]
+pub struct HaskellRequired {
+    pub ghc_version: String,
+    pub installed_versions: Vec<String>,
+    pub libraries: Vec<String>,
+    pub files_requiring: Vec<Rc<String>>,
+}
+
+#[derive(Debug)]
+pub struct VersionRequired {
+    pub version: MissingVersion,
+    pub files_requiring: Vec<Rc<String>>,
+}
+
+#[derive(Debug, Default)]
+pub struct SymbolRequired {
+    pub symbol_name: Rc<String>,
+    pub files_requiring: Vec<Rc<String>>,
+}
+
 #[derive(Debug, Default)]
 pub struct PackagesContaining {
     pub library_name: Rc<String>,
-    pub packages_containing: Vec<String>,
+    pub packages_containing: Vec<PackageCandidate>,
+}
+
+/// Files installed for a version of an interpreter which is not installed anymore
+#[derive(Debug)]
+pub struct RuntimeDependency {
+    pub runtime: String,
+    pub version: String,
+    pub installed_version: Option<String>,
+    pub files: Vec<String>,
+}
+
+/// Installed libraries which differ only in version from a missing library,
+/// usually because the providing package bumped the soname
+#[derive(Debug, Default)]
+pub struct LibraryReplacement {
+    pub library_name: Rc<String>,
+    pub replaced_by: Vec<InstalledLibrary>,
+}
+
+/// A file of a repository package which might satisfy a missing library
+#[derive(Debug)]
+pub struct PackageCandidate {
+    pub file: PackageFile,
+    /// the file is placed in a directory searched by the dynamic linker
+    pub on_linker_path: bool,
+    /// the directory holds libraries usable by the files requiring the library
+    pub architecture_matches: bool,
+}
+
+impl PackageCandidate {
+    /// A candidate satisfies the library requirement once installed
+    pub const fn is_usable(&self) -> bool {
+        self.on_linker_path && self.architecture_matches
+    }
 }
@@ -0,0 +1,531 @@
+use crate::{archive, data::Error, root};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Specifies why a package was installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallReason {
+    Explicit,
+    Dependency,
+}
+
+impl fmt::Display for InstallReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Explicit => write!(f, "explicit"),
+            Self::Dependency => write!(f, "dependency"),
+        }
+    }
+}
+
+/// A package installed on the system as described by its `desc` file
+#[derive(Debug)]
+pub struct LocalPackage {
+    pub name: String,
+    pub version: String,
+    pub reason: InstallReason,
+    pub validation: Vec<String>,
+    /// virtual packages and versioned sonames like `libfoo.so=3-64`
+    pub provides: Vec<String>,
+    /// the declared dependencies including version constraints like `foo>=1.0`
+    pub depends: Vec<String>,
+    directory: PathBuf,
+}
+
+/// The database of a repository as downloaded by `pacman -Sy`. It is
+/// usually located at `/var/lib/pacman/sync/<repository>.db`
+#[derive(Debug)]
+pub struct SyncDatabase {
+    /// the versions of all packages in the repository by package name
+    versions: HashMap<String, String>,
+}
+
+/// A file of a package available in one of the repositories
+#[derive(Debug, Clone)]
+pub struct PackageFile {
+    pub repository: String,
+    pub package: String,
+    pub path: String,
+}
+
+/// Maps the names of files to the repository packages containing them,
+/// like `pkgfile` does
+#[derive(Debug)]
+pub struct FileIndex {
+    files: HashMap<String, Vec<PackageFile>>,
+}
+
+/// A library of an installed package sharing the stem of a missing library
+#[derive(Debug, Clone)]
+pub struct InstalledLibrary {
+    pub package: String,
+    pub library: String,
+}
+
+/// Maps library stems like `libfoo.so` to the installed libraries
+/// with the same stem, regardless of their version
+#[derive(Debug)]
+pub struct LibraryIndex {
+    libraries: HashMap<String, Vec<InstalledLibrary>>,
+}
+
+/// Maps the files of all installed packages to the package owning them
+#[derive(Debug)]
+pub struct OwnerIndex {
+    owners: HashMap<String, String>,
+}
+
+/// The database pacman uses to keep track of installed packages.
+/// It is usually located at `/var/lib/pacman/local`
+#[derive(Debug)]
+pub struct LocalDatabase {
+    root: PathBuf,
+    packages: HashMap<String, LocalPackage>,
+}
+
+impl LocalDatabase {
+    /// Reads the description of every package in the local database
+    /// below `db_path`. Files of packages are relative to `root`
+    pub fn read<'a>(db_path: &Path, root: &Path) -> Result<Self, Error<'a>> {
+        let mut packages = HashMap::new();
+        for entry in fs::read_dir(db_path.join("local"))? {
+            let directory = entry?.path();
+            // the database contains a version file next to the package directories
+            if !directory.is_dir() {
+                continue;
+            }
+            let desc = parse_desc(&fs::read_to_string(directory.join("desc"))?);
+            let package = LocalPackage::from_desc(desc, directory)?;
+            let _ = packages.insert(package.name.clone(), package);
+        }
+        Ok(Self {
+            root: root.to_path_buf(),
+            packages,
+        })
+    }
+
+    pub fn packages(&self) -> impl Iterator<Item = &LocalPackage> {
+        self.packages.values()
+    }
+
+    pub fn package<'a>(&self, name: &str) -> Result<&LocalPackage, Error<'a>> {
+        self.packages
+            .get(name)
+            .ok_or_else(|| Error::PackageNotFound(String::from(name)))
+    }
+
+    /// Returns the names of the packages satisfying `depends` and all of their
+    /// dependencies. Dependencies are satisfied by package names and `provides`
+    pub fn dependency_closure(&self, depends: &[String]) -> HashSet<String> {
+        let mut closure = HashSet::new();
+        let mut pending = depends.iter().collect::<Vec<&String>>();
+        while let Some(depend) = pending.pop() {
+            let Some(package) = self.satisfier(dependency_name(depend)) else {
+                continue;
+            };
+            if closure.insert(package.name.clone()) {
+                pending.extend(&package.depends);
+            }
+        }
+        closure
+    }
+
+    /// Returns the installed package named `name` or providing `name`
+    fn satisfier(&self, name: &str) -> Option<&LocalPackage> {
+        self.packages.get(name).or_else(|| {
+            self.packages().find(|package| {
+                package
+                    .provides
+                    .iter()
+                    .any(|provide| dependency_name(provide) == name)
+            })
+        })
+    }
+
+    /// Returns the absolute paths of the files and directories of every package
+    pub fn owned_files<'a>(&self) -> Result<HashSet<String>, Error<'a>> {
+        let mut owned = HashSet::new();
+        for package in self.packages() {
+            owned.extend(self.files(package)?);
+        }
+        Ok(owned)
+    }
+
+    /// Returns the absolute paths of all files and directories owned by `package`.
+    /// The directories are resolved inside of the root, the files keep their names
+    pub fn files<'a>(&self, package: &LocalPackage) -> Result<Vec<String>, Error<'a>> {
+        let mut files = parse_desc(&fs::read_to_string(package.directory.join("files"))?);
+        Ok(files
+            .remove("FILES")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|file| {
+                let path = Path::new("/").join(&file);
+                let mut located = match (path.parent(), path.file_name()) {
+                    (Some(directory), Some(name)) => root::join(&self.root, directory).join(name),
+                    _ => self.root.clone(),
+                }
+                .to_string_lossy()
+                .into_owned();
+                // directories are marked by a trailing slash
+                if file.ends_with('/') {
+                    located.push('/');
+                }
+                located
+            })
+            .collect())
+    }
+}
+
+impl SyncDatabase {
+    /// Reads every repository database in the `sync` directory below `db_path`.
+    /// A missing directory is treated as a system without repositories
+    pub fn read_all<'a>(db_path: &Path) -> Result<Vec<Self>, Error<'a>> {
+        let Ok(entries) = fs::read_dir(db_path.join("sync")) else {
+            return Ok(vec![]);
+        };
+        let mut databases = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "db") {
+                databases.push(Self::read(&path)?);
+            }
+        }
+        Ok(databases)
+    }
+
+    fn read<'a>(path: &Path) -> Result<Self, Error<'a>> {
+        let mut versions = HashMap::new();
+        archive::read_archive(path, |entry, content| {
+            if !entry.ends_with("/desc") {
+                return Ok(());
+            }
+            let mut desc = parse_desc(&String::from_utf8_lossy(content));
+            let mut single_value = |key| desc.remove(key).and_then(|mut values| values.pop());
+            let (Some(name), Some(version)) = (single_value("NAME"), single_value("VERSION"))
+            else {
+                return Err(Error::Database(format!(
+                    "{}: {}",
+                    path.to_string_lossy(),
+                    entry
+                )));
+            };
+            let _ = versions.insert(name, version);
+            Ok(())
+        })?;
+        Ok(Self { versions })
+    }
+}
+
+impl FileIndex {
+    /// Reads the files databases of all repositories (`pacman -Fy`) below `db_path`
+    /// and remembers which packages contain a file named like one of `file_names`
+    pub fn read<'a>(db_path: &Path, file_names: &HashSet<String>) -> Result<Self, Error<'a>> {
+        let mut databases = fs::read_dir(db_path.join("sync"))
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| {
+                        path.extension()
+                            .is_some_and(|extension| extension == "files")
+                    })
+                    .collect::<Vec<PathBuf>>()
+            })
+            .unwrap_or_default();
+        if databases.is_empty() {
+            return Err(Error::Dependency("pacman files databases (pacman -Fy)"));
+        }
+        databases.sort();
+
+        let mut files: HashMap<String, Vec<PackageFile>> = HashMap::new();
+        for database in databases {
+            let repository = database
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            // the desc and files entries of a package are not guaranteed to be in order
+            let mut names = HashMap::new();
+            let mut matches = Vec::new();
+            archive::read_archive(&database, |entry, content| {
+                let Some((directory, kind)) = entry
+                    .rsplit_once('/')
+                    .filter(|(_, kind)| *kind == "desc" || *kind == "files")
+                else {
+                    return Ok(());
+                };
+                let mut desc = parse_desc(&String::from_utf8_lossy(content));
+                match kind {
+                    "desc" => {
+                        if let Some(name) = desc.remove("NAME").and_then(|mut name| name.pop()) {
+                            let _ = names.insert(String::from(directory), name);
+                        }
+                    }
+                    "files" => matches.extend(
+                        desc.remove("FILES")
+                            .unwrap_or_default()
+                            .into_iter()
+                            .filter(|file| {
+                                Path::new(file).file_name().is_some_and(|file_name| {
+                                    file_names.contains(&*file_name.to_string_lossy())
+                                })
+                            })
+                            .map(|file| (String::from(directory), file)),
+                    ),
+                    _ => {}
+                }
+                Ok(())
+            })?;
+            for (directory, file) in matches {
+                let (Some(package), Some(file_name)) =
+                    (names.get(&directory), Path::new(&file).file_name())
+                else {
+                    continue;
+                };
+                files
+                    .entry(file_name.to_string_lossy().into_owned())
+                    .or_default()
+                    .push(PackageFile {
+                        repository: repository.clone(),
+                        package: package.clone(),
+                        path: format!("/{}", file),
+                    });
+            }
+        }
+        Ok(Self { files })
+    }
+
+    /// Returns all files named `file_name` in any repository
+    pub fn find(&self, file_name: &str) -> Vec<PackageFile> {
+        self.files.get(file_name).cloned().unwrap_or_default()
+    }
+}
+
+impl OwnerIndex {
+    /// Reads the file lists of all installed packages. Directories are shared
+    /// between packages and are not indexed
+    pub fn read<'a>(database: &LocalDatabase) -> Result<Self, Error<'a>> {
+        let mut owners = HashMap::new();
+        for package in database.packages() {
+            for file in database.files(package)? {
+                if !file.ends_with('/') {
+                    let _ = owners.insert(file, package.name.clone());
+                }
+            }
+        }
+        Ok(Self { owners })
+    }
+
+    /// Returns the package owning the file at `path`. Symlinks like `/lib`
+    /// pointing to `/usr/lib` are resolved if `path` is not listed itself
+    pub fn owner(&self, path: &Path) -> Option<&str> {
+        self.owners
+            .get(path.to_string_lossy().as_ref())
+            .or_else(|| {
+                let directory = fs::canonicalize(path.parent()?).ok()?;
+                let path = directory.join(path.file_name()?);
+                self.owners.get(path.to_string_lossy().as_ref())
+            })
+            .map(String::as_str)
+    }
+}
+
+impl LibraryIndex {
+    /// Collects the libraries of all installed packages whose stem is one of `stems`.
+    /// Both the files of the packages and their `provides` entries are considered
+    pub fn read<'a>(database: &LocalDatabase, stems: &HashSet<String>) -> Result<Self, Error<'a>> {
+        let mut libraries: HashMap<String, Vec<InstalledLibrary>> = HashMap::new();
+        for package in database.packages() {
+            let files = database.files(package)?;
+            let file_names = files
+                .iter()
+                .filter_map(|file| Path::new(file).file_name())
+                .map(|file_name| file_name.to_string_lossy().into_owned());
+            // `libfoo.so=3-64` provides `libfoo.so.3` for 64 bit files
+            let provided = package.provides.iter().filter_map(|provide| {
+                let (stem, version) = provide.split_once('=')?;
+                let version = version.split('-').next()?;
+                Some(format!("{}.{}", stem, version))
+            });
+            let found = file_names
+                .chain(provided)
+                .filter(|library| {
+                    library_stem(library)
+                        .is_some_and(|stem| stem != library && stems.contains(stem))
+                })
+                .collect::<HashSet<String>>();
+            // `libfoo.so.3.0.0` is only the file behind the soname `libfoo.so.3`
+            for library in found.iter().filter(|library| {
+                !found
+                    .iter()
+                    .any(|soname| library.starts_with(&format!("{}.", soname)))
+            }) {
+                if let Some(stem) = library_stem(library) {
+                    libraries
+                        .entry(String::from(stem))
+                        .or_default()
+                        .push(InstalledLibrary {
+                            package: package.name.clone(),
+                            library: library.clone(),
+                        });
+                }
+            }
+        }
+        Ok(Self { libraries })
+    }
+
+    /// Returns the installed libraries sharing the stem of `library` without
+    /// being `library` itself
+    pub fn find(&self, library: &str) -> Vec<InstalledLibrary> {
+        library_stem(library)
+            .and_then(|stem| self.libraries.get(stem))
+            .map(|libraries| {
+                libraries
+                    .iter()
+                    .filter(|installed| installed.library != library)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Strips the version constraint of a dependency: `foo>=1.0` becomes `foo`
+pub fn dependency_name(depend: &str) -> &str {
+    depend
+        .find(['<', '>', '='])
+        .and_then(|index| depend.get(..index))
+        .unwrap_or(depend)
+}
+
+/// Strips the version of a soname: `libfoo.so.2` becomes `libfoo.so`
+pub fn library_stem(library: &str) -> Option<&str> {
+    library
+        .find(".so")
+        .and_then(|index| library.get(..index + 3))
+        .filter(|stem| {
+            library
+                .get(stem.len()..)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+}
+
+/// Returns the names of all installed packages which are not available in any
+/// repository, like `pacman -Qm`. With `include_outdated` packages whose installed
+/// version differs from the version in the repositories are returned as well
+pub fn get_foreign_packages(
+    local_database: &LocalDatabase,
+    sync_databases: &[SyncDatabase],
+    include_outdated: bool,
+) -> Vec<String> {
+    let mut packages = local_database
+        .packages()
+        .filter(|package| {
+            let mut versions = sync_databases
+                .iter()
+                .filter_map(|database| database.versions.get(&package.name))
+                .peekable();
+            if versions.peek().is_none() {
+                return true;
+            }
+            include_outdated && versions.all(|version| *version != package.version)
+        })
+        .map(|package| package.name.clone())
+        .collect::<Vec<String>>();
+    packages.sort();
+    packages
+}
+
+impl LocalPackage {
+    fn from_desc<'a>(
+        mut desc: HashMap<String, Vec<String>>,
+        directory: PathBuf,
+    ) -> Result<Self, Error<'a>> {
+        let mut single_value = |key| desc.remove(key).and_then(|mut values| values.pop());
+        let (Some(name), Some(version)) = (single_value("NAME"), single_value("VERSION")) else {
+            return Err(Error::Database(directory.to_string_lossy().into_owned()));
+        };
+        // packages installed before install reasons were tracked are explicit
+        let reason = match single_value("REASON").as_deref() {
+            Some("1") => InstallReason::Dependency,
+            _ => InstallReason::Explicit,
+        };
+        Ok(Self {
+            name,
+            version,
+            reason,
+            validation: desc.remove("VALIDATION").unwrap_or_default(),
+            provides: desc.remove("PROVIDES").unwrap_or_default(),
+            depends: desc.remove("DEPENDS").unwrap_or_default(),
+            directory,
+        })
+    }
+}
+
+/// Parses the `%KEY%` blocks used by the `desc` and `files` entries of a database
+pub fn parse_desc(content: &str) -> HashMap<String, Vec<String>> {
+    let mut fields: HashMap<String, Vec<String>> = HashMap::new();
+    let mut key = None;
+    for line in content.lines() {
+        let name = line
+            .strip_prefix('%')
+            .and_then(|line| line.strip_suffix('%'))
+            .filter(|name| !name.is_empty());
+        if let Some(name) = name {
+            let _ = fields.entry(String::from(name)).or_default();
+            key = Some(String::from(name));
+        } else if !line.is_empty() {
+            if let Some(ref key) = key {
+                fields
+                    .entry(key.clone())
+                    .or_default()
+                    .push(String::from(line));
+            }
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desc_blocks_are_parsed() {
+        let desc = parse_desc(
+            "%NAME%\nfoo\n\n%DEPENDS%\nbar>=1.0\nbaz\n\n%EMPTY%\n\n%PROVIDES%\nlibfoo.so=1-64\n%%\n",
+        );
+        assert_eq!(desc["NAME"], vec![String::from("foo")]);
+        assert_eq!(
+            desc["DEPENDS"],
+            vec![String::from("bar>=1.0"), String::from("baz")]
+        );
+        assert!(desc["EMPTY"].is_empty());
+        // `%%` is no key but a value of the previous one
+        assert_eq!(
+            desc["PROVIDES"],
+            vec![String::from("libfoo.so=1-64"), String::from("%%")]
+        );
+        assert!(parse_desc("lines before\nthe first key\n").is_empty());
+    }
+
+    #[test]
+    fn dependency_names_lose_their_constraint() {
+        assert_eq!(dependency_name("foo>=1.0"), "foo");
+        assert_eq!(dependency_name("foo<2"), "foo");
+        assert_eq!(dependency_name("libfoo.so=1-64"), "libfoo.so");
+        assert_eq!(dependency_name("foo"), "foo");
+    }
+
+    #[test]
+    fn library_stems_lose_their_version() {
+        assert_eq!(library_stem("libfoo.so.2"), Some("libfoo.so"));
+        assert_eq!(library_stem("libfoo.so.2.1.0"), Some("libfoo.so"));
+        assert_eq!(library_stem("libfoo.so"), Some("libfoo.so"));
+        assert_eq!(library_stem("libfoo.sonic.so.1"), None);
+        assert_eq!(library_stem("libfoo.a"), None);
+    }
+}
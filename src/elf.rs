@@ -0,0 +1,698 @@
+use crate::data::Error;
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    fmt,
+    fs::{self, File},
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+
+/// Size of `e_ident`, `e_type` and `e_machine` which are identical for all classes
+const IDENTIFICATION_SIZE: u64 = 20;
+const EM_NONE: u16 = 0;
+/// The names of the common values of `e_machine`
+const MACHINE_NAMES: [(u16, &str); 10] = [
+    (2, "sparc"),
+    (3, "x86"),
+    (8, "mips"),
+    (20, "powerpc"),
+    (21, "powerpc64"),
+    (22, "s390"),
+    (40, "arm"),
+    (62, "x86-64"),
+    (183, "aarch64"),
+    (243, "riscv"),
+];
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_HASH: u64 = 4;
+const DT_STRTAB: u64 = 5;
+const DT_SYMTAB: u64 = 6;
+const DT_STRSZ: u64 = 10;
+const DT_SYMENT: u64 = 11;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+const DT_GNU_HASH: u64 = 0x6fff_fef5;
+const DT_VERDEF: u64 = 0x6fff_fffc;
+const DT_VERDEFNUM: u64 = 0x6fff_fffd;
+const DT_VERNEED: u64 = 0x6fff_fffe;
+const DT_VERNEEDNUM: u64 = 0x6fff_ffff;
+
+const SHN_UNDEF: u16 = 0;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const STB_GNU_UNIQUE: u8 = 10;
+const STV_DEFAULT: u8 = 0;
+const STV_PROTECTED: u8 = 3;
+
+/// The version definition naming the file itself instead of a version
+const VER_FLG_BASE: u16 = 1;
+/// Missing weak versions only cause a warning of the dynamic linker
+const VER_FLG_WEAK: u16 = 2;
+const VERNEED_SIZE: u64 = 16;
+const VERNAUX_SIZE: u64 = 16;
+const VERDEF_SIZE: u64 = 20;
+
+/// The word size of an elf file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    Elf32,
+    Elf64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Endianness {
+    Little,
+    Big,
+}
+
+/// Libraries can only be loaded by files sharing the same architecture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Architecture {
+    pub class: Class,
+    pub machine: u16,
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = match self.class {
+            Class::Elf32 => 32,
+            Class::Elf64 => 64,
+        };
+        match MACHINE_NAMES
+            .iter()
+            .find(|(machine, _)| *machine == self.machine)
+        {
+            Some((_, name)) => write!(f, "{}-bit {}", bits, name),
+            None => write!(f, "{}-bit machine {}", bits, self.machine),
+        }
+    }
+}
+
+/// The information of an elf file required to find its libraries
+#[derive(Debug)]
+pub struct ElfFile {
+    pub architecture: Architecture,
+    /// programs request an interpreter (`PT_INTERP`) while shared objects do not
+    pub executable: bool,
+    pub needed: Vec<String>,
+    pub rpath: Vec<String>,
+    pub runpath: Vec<String>,
+    /// the symbol versions required from each library (`.gnu.version_r`)
+    pub version_requirements: Vec<VersionRequirement>,
+    /// the symbol versions defined by the file (`.gnu.version_d`)
+    pub version_definitions: Vec<String>,
+}
+
+/// The dynamic symbols of an elf file
+#[derive(Debug, Default)]
+pub struct Symbols {
+    /// symbols which are neither weak nor defined by the file itself
+    pub undefined: Vec<String>,
+    /// symbols the file exports to other files
+    pub defined: HashSet<String>,
+}
+
+/// The symbol versions an elf file requires from one of its libraries
+#[derive(Debug, Clone)]
+pub struct VersionRequirement {
+    pub library: String,
+    pub versions: Vec<String>,
+}
+
+/// The location of the dynamic string table
+#[derive(Clone, Copy)]
+struct StringTable {
+    offset: u64,
+    end: u64,
+}
+
+/// The tag and value of an entry of the `PT_DYNAMIC` segment
+type DynamicEntry = (u64, u64);
+
+struct Segment {
+    kind: u32,
+    offset: u64,
+    address: u64,
+    size: u64,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    class: Class,
+    endianness: Endianness,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: u64) -> Option<[u8; N]> {
+        let start = usize::try_from(offset).ok()?;
+        let bytes = self.data.get(start..start.checked_add(N)?)?;
+        <[u8; N]>::try_from(bytes).ok()
+    }
+
+    fn u16(&self, offset: u64) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(match self.endianness {
+            Endianness::Little => u16::from_le_bytes(bytes),
+            Endianness::Big => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(match self.endianness {
+            Endianness::Little => u32::from_le_bytes(bytes),
+            Endianness::Big => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Option<u64> {
+        let bytes = self.bytes(offset)?;
+        Some(match self.endianness {
+            Endianness::Little => u64::from_le_bytes(bytes),
+            Endianness::Big => u64::from_be_bytes(bytes),
+        })
+    }
+
+    /// Reads an address sized value (`Elf32_Addr`/`Elf64_Addr`, `Elf32_Off`/`Elf64_Off`)
+    fn word(&self, offset: u64) -> Option<u64> {
+        match self.class {
+            Class::Elf32 => self.u32(offset).map(u64::from),
+            Class::Elf64 => self.u64(offset),
+        }
+    }
+
+    fn string(&self, offset: u64, end: u64) -> Option<String> {
+        let start = usize::try_from(offset).ok()?;
+        let end = usize::try_from(end).ok()?.min(self.data.len());
+        let bytes = self.data.get(start..end)?;
+        let length = bytes.iter().position(|byte| *byte == 0)?;
+        Some(String::from_utf8_lossy(bytes.get(..length)?).into_owned())
+    }
+
+    fn table_string(&self, table: StringTable, index: u32) -> Option<String> {
+        self.string(table.offset.checked_add(u64::from(index))?, table.end)
+    }
+
+    /// Follows the `next` field of a version entry, which is relative to the entry
+    fn next_entry(&self, entry: u64, field: u64) -> Option<u64> {
+        entry.checked_add(u64::from(self.u32(entry.checked_add(field)?)?))
+    }
+
+    /// Returns the number of entries of `size` bytes fitting into the file. A
+    /// malformed count can not claim more entries than that
+    fn max_entries(&self, size: u64) -> u64 {
+        u64::try_from(self.data.len()).unwrap_or(u64::MAX) / size
+    }
+
+    /// Reads `count` `Elf_Verneed` entries starting at `offset`. Their layout
+    /// is identical for all classes. Like in glibc a `next` field of 0 ends the chain
+    fn version_requirements(
+        &self,
+        offset: u64,
+        count: u64,
+        strings: StringTable,
+    ) -> Option<Vec<VersionRequirement>> {
+        let mut requirements = Vec::new();
+        let mut entry = offset;
+        for _ in 0..count.min(self.max_entries(VERNEED_SIZE)) {
+            let library = self.table_string(strings, self.u32(entry.checked_add(4)?)?)?;
+            let mut versions = Vec::new();
+            let mut auxiliary = self.next_entry(entry, 8)?;
+            let auxiliary_count = u64::from(self.u16(entry.checked_add(2)?)?);
+            for _ in 0..auxiliary_count.min(self.max_entries(VERNAUX_SIZE)) {
+                if self.u16(auxiliary.checked_add(4)?)? & VER_FLG_WEAK == 0 {
+                    versions
+                        .push(self.table_string(strings, self.u32(auxiliary.checked_add(8)?)?)?);
+                }
+                if self.u32(auxiliary.checked_add(12)?)? == 0 {
+                    break;
+                }
+                auxiliary = self.next_entry(auxiliary, 12)?;
+            }
+            requirements.push(VersionRequirement { library, versions });
+            if self.u32(entry.checked_add(12)?)? == 0 {
+                break;
+            }
+            entry = self.next_entry(entry, 12)?;
+        }
+        Some(requirements)
+    }
+
+    /// Reads the names of `count` `Elf_Verdef` entries starting at `offset`
+    fn version_definitions(
+        &self,
+        offset: u64,
+        count: u64,
+        strings: StringTable,
+    ) -> Option<Vec<String>> {
+        let mut definitions = Vec::new();
+        let mut entry = offset;
+        for _ in 0..count.min(self.max_entries(VERDEF_SIZE)) {
+            if self.u16(entry.checked_add(2)?)? & VER_FLG_BASE == 0 {
+                let auxiliary = self.next_entry(entry, 12)?;
+                definitions.push(self.table_string(strings, self.u32(auxiliary)?)?);
+            }
+            if self.u32(entry.checked_add(16)?)? == 0 {
+                break;
+            }
+            entry = self.next_entry(entry, 16)?;
+        }
+        Some(definitions)
+    }
+
+    /// Determines the number of dynamic symbols. The section headers might be
+    /// stripped, so the hash tables used by the dynamic linker are read instead
+    fn symbol_count(&self, segments: &[Segment], entries: &[(u64, u64)]) -> Option<u64> {
+        if let Some(address) = find_entry(entries, DT_HASH) {
+            // the number of chain entries equals the number of symbols
+            let hash = address_to_offset(segments, address)?;
+            return self.u32(hash.checked_add(4)?).map(u64::from);
+        }
+        let hash = address_to_offset(segments, find_entry(entries, DT_GNU_HASH)?)?;
+        let bucket_count = u64::from(self.u32(hash)?);
+        let symbol_offset = u64::from(self.u32(hash.checked_add(4)?)?);
+        let bloom_size = u64::from(self.u32(hash.checked_add(8)?)?);
+        let word_size = match self.class {
+            Class::Elf32 => 4,
+            Class::Elf64 => 8,
+        };
+        let buckets = hash
+            .checked_add(16)?
+            .checked_add(bloom_size.checked_mul(word_size)?)?;
+        let last_bucket = (0..bucket_count)
+            .map(|index| {
+                Some(u64::from(
+                    self.u32(buckets.checked_add(index.checked_mul(4)?)?)?,
+                ))
+            })
+            .collect::<Option<Vec<u64>>>()?
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+        if last_bucket < symbol_offset {
+            return Some(symbol_offset);
+        }
+        // the chain of the last bucket ends with the last symbol, marked by the lowest bit
+        let chains = buckets.checked_add(bucket_count.checked_mul(4)?)?;
+        let mut index = last_bucket;
+        loop {
+            let chain = chains.checked_add((index.checked_sub(symbol_offset)?).checked_mul(4)?)?;
+            let hash = self.u32(chain)?;
+            index = index.checked_add(1)?;
+            if hash & 1 != 0 {
+                return Some(index);
+            }
+        }
+    }
+
+    /// Returns the content of the section called `name`
+    fn section(&self, name: &str) -> Option<&[u8]> {
+        let (shoff, shentsize, shnum, shstrndx) = match self.class {
+            Class::Elf32 => (self.word(32)?, self.u16(46)?, self.u16(48)?, self.u16(50)?),
+            Class::Elf64 => (self.word(40)?, self.u16(58)?, self.u16(60)?, self.u16(62)?),
+        };
+        let header = |index: u64| shoff.checked_add(index.checked_mul(u64::from(shentsize))?);
+        let location = |header: u64| match self.class {
+            Class::Elf32 => Some((
+                self.word(header.checked_add(16)?)?,
+                self.word(header.checked_add(20)?)?,
+            )),
+            Class::Elf64 => Some((
+                self.word(header.checked_add(24)?)?,
+                self.word(header.checked_add(32)?)?,
+            )),
+        };
+        let (names, names_size) = location(header(u64::from(shstrndx))?)?;
+        let names_end = names.checked_add(names_size)?;
+        let (offset, size) = (0..u64::from(shnum)).find_map(|index| {
+            let header = header(index)?;
+            let name_offset = names.checked_add(u64::from(self.u32(header)?))?;
+            if self.string(name_offset, names_end)? == name {
+                location(header)
+            } else {
+                None
+            }
+        })?;
+        let start = usize::try_from(offset).ok()?;
+        let end = usize::try_from(offset.checked_add(size)?).ok()?;
+        self.data.get(start..end)
+    }
+
+    fn architecture(&self) -> Option<Architecture> {
+        Some(Architecture {
+            class: self.class,
+            machine: self.u16(18)?,
+        })
+    }
+
+    fn segments(&self) -> Option<Vec<Segment>> {
+        let (phoff, phentsize, phnum) = match self.class {
+            Class::Elf32 => (self.word(28)?, self.u16(42)?, self.u16(44)?),
+            Class::Elf64 => (self.word(32)?, self.u16(54)?, self.u16(56)?),
+        };
+        (0..u64::from(phnum))
+            .map(|index| {
+                let header = phoff.checked_add(index.checked_mul(u64::from(phentsize))?)?;
+                let field = |delta: u64| header.checked_add(delta);
+                Some(match self.class {
+                    Class::Elf32 => Segment {
+                        kind: self.u32(header)?,
+                        offset: self.word(field(4)?)?,
+                        address: self.word(field(8)?)?,
+                        size: self.word(field(16)?)?,
+                    },
+                    Class::Elf64 => Segment {
+                        kind: self.u32(header)?,
+                        offset: self.word(field(8)?)?,
+                        address: self.word(field(16)?)?,
+                        size: self.word(field(32)?)?,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the tag-value pairs stored in the `PT_DYNAMIC` segment
+    fn dynamic_entries(&self, dynamic: &Segment) -> Option<Vec<(u64, u64)>> {
+        let entry_size = match self.class {
+            Class::Elf32 => 8,
+            Class::Elf64 => 16,
+        };
+        let mut entries = Vec::new();
+        let mut offset = dynamic.offset;
+        let end = dynamic.offset.checked_add(dynamic.size)?;
+        while offset.checked_add(entry_size)? <= end {
+            let tag = self.word(offset)?;
+            if tag == DT_NULL {
+                break;
+            }
+            entries.push((tag, self.word(offset + entry_size / 2)?));
+            offset += entry_size;
+        }
+        Some(entries)
+    }
+}
+
+/// Translates a virtual address into an offset inside the file
+fn address_to_offset(segments: &[Segment], address: u64) -> Option<u64> {
+    segments
+        .iter()
+        .filter(|segment| segment.kind == PT_LOAD)
+        .find(|segment| address >= segment.address && address - segment.address < segment.size)
+        .and_then(|segment| segment.offset.checked_add(address - segment.address))
+}
+
+/// Reads and parses the elf file at `path`. Returns `None` if the file
+/// is not an elf file or cannot be parsed
+pub fn read_elf<'a>(path: &Path) -> Result<Option<ElfFile>, Error<'a>> {
+    let data = fs::read(path)?;
+    Ok(parse_elf(&data))
+}
+
+/// Checks with a single small read whether `path` is an executable or a shared
+/// object which may require libraries. Relocatable and core files are ignored
+pub fn file_is_elf<'a>(path: &Path) -> Result<bool, Error<'a>> {
+    let mut header = Vec::new();
+    match File::open(path) {
+        Ok(file) => {
+            let _ = file.take(IDENTIFICATION_SIZE).read_to_end(&mut header)?;
+        }
+        // the dynamic linker would not be able to read the file either
+        Err(ref error) if error.kind() == ErrorKind::PermissionDenied => return Ok(false),
+        Err(error) => return Err(error.into()),
+    }
+    Ok(data_is_elf(&header))
+}
+
+/// Checks whether `data` starts with the header of an executable or a shared object
+pub fn data_is_elf(data: &[u8]) -> bool {
+    reader(data).is_some_and(|reader| {
+        reader.u16(18).is_some_and(|machine| machine != EM_NONE)
+            && reader
+                .u16(16)
+                .is_some_and(|kind| kind == ET_EXEC || kind == ET_DYN)
+    })
+}
+
+fn reader(data: &[u8]) -> Option<Reader<'_>> {
+    if data.get(..4)? != ELF_MAGIC {
+        return None;
+    }
+    let class = match *data.get(4)? {
+        ELFCLASS32 => Class::Elf32,
+        ELFCLASS64 => Class::Elf64,
+        _ => return None,
+    };
+    let endianness = match *data.get(5)? {
+        ELFDATA2LSB => Endianness::Little,
+        ELFDATA2MSB => Endianness::Big,
+        _ => return None,
+    };
+    Some(Reader {
+        data,
+        class,
+        endianness,
+    })
+}
+
+/// Reads the segments and the entries of the `PT_DYNAMIC` segment. Statically
+/// linked files have no dynamic entries
+fn dynamic(reader: &Reader<'_>) -> Option<(Vec<Segment>, Vec<DynamicEntry>)> {
+    let segments = reader.segments()?;
+    let entries = match segments.iter().find(|segment| segment.kind == PT_DYNAMIC) {
+        Some(dynamic) => reader.dynamic_entries(dynamic)?,
+        None => vec![],
+    };
+    Some((segments, entries))
+}
+
+fn find_entry(entries: &[(u64, u64)], tag: u64) -> Option<u64> {
+    entries
+        .iter()
+        .find(|(entry_tag, _)| *entry_tag == tag)
+        .map(|(_, value)| *value)
+}
+
+fn string_table(segments: &[Segment], entries: &[(u64, u64)]) -> Option<StringTable> {
+    let offset = address_to_offset(segments, find_entry(entries, DT_STRTAB)?)?;
+    Some(StringTable {
+        offset,
+        end: offset.checked_add(find_entry(entries, DT_STRSZ)?)?,
+    })
+}
+
+/// Parses the dynamic section of an elf file
+pub fn parse_elf(data: &[u8]) -> Option<ElfFile> {
+    let reader = reader(data)?;
+    let (segments, entries) = dynamic(&reader)?;
+    let mut elf = ElfFile {
+        architecture: reader.architecture()?,
+        executable: segments.iter().any(|segment| segment.kind == PT_INTERP),
+        needed: vec![],
+        rpath: vec![],
+        runpath: vec![],
+        version_requirements: vec![],
+        version_definitions: vec![],
+    };
+    // statically linked files do not require any library
+    if entries.is_empty() {
+        return Some(elf);
+    }
+    let strings = string_table(&segments, &entries)?;
+    for (tag, value) in &entries {
+        let string = || reader.string(strings.offset.checked_add(*value)?, strings.end);
+        match *tag {
+            DT_NEEDED => elf.needed.push(string()?),
+            DT_RPATH => elf.rpath.extend(split_search_path(&string()?)),
+            DT_RUNPATH => elf.runpath.extend(split_search_path(&string()?)),
+            _ => {}
+        }
+    }
+    if let (Some(address), Some(count)) = (
+        find_entry(&entries, DT_VERNEED),
+        find_entry(&entries, DT_VERNEEDNUM),
+    ) {
+        let offset = address_to_offset(&segments, address)?;
+        elf.version_requirements = reader.version_requirements(offset, count, strings)?;
+    }
+    if let (Some(address), Some(count)) = (
+        find_entry(&entries, DT_VERDEF),
+        find_entry(&entries, DT_VERDEFNUM),
+    ) {
+        let offset = address_to_offset(&segments, address)?;
+        elf.version_definitions = reader.version_definitions(offset, count, strings)?;
+    }
+    Some(elf)
+}
+
+/// Reads the dynamic symbols of the elf file at `path`. Returns `None` if the
+/// file is not an elf file or cannot be parsed
+pub fn read_symbols<'a>(path: &Path) -> Result<Option<Symbols>, Error<'a>> {
+    let data = fs::read(path)?;
+    Ok(parse_symbols(&data))
+}
+
+/// Parses the dynamic symbol table (`.dynsym`) of an elf file
+pub fn parse_symbols(data: &[u8]) -> Option<Symbols> {
+    let reader = reader(data)?;
+    let (segments, entries) = dynamic(&reader)?;
+    let mut symbols = Symbols::default();
+    let Some(address) = find_entry(&entries, DT_SYMTAB) else {
+        return Some(symbols);
+    };
+    let table = address_to_offset(&segments, address)?;
+    let strings = string_table(&segments, &entries)?;
+    let (default_size, name, info, other, section) = match reader.class {
+        Class::Elf32 => (16, 0, 12, 13, 14),
+        Class::Elf64 => (24, 0, 4, 5, 6),
+    };
+    let entry_size = find_entry(&entries, DT_SYMENT).unwrap_or(default_size);
+    // the first symbol is always the undefined null symbol
+    for index in 1..reader.symbol_count(&segments, &entries)? {
+        let symbol = table.checked_add(index.checked_mul(entry_size)?)?;
+        let field = |delta: u64| symbol.checked_add(delta);
+        let [info] = reader.bytes::<1>(field(info)?)?;
+        let [other] = reader.bytes::<1>(field(other)?)?;
+        let binding = info >> 4;
+        let visibility = other & 3;
+        let symbol_name = reader.table_string(strings, reader.u32(field(name)?)?)?;
+        if symbol_name.is_empty() {
+            continue;
+        }
+        if reader.u16(field(section)?)? == SHN_UNDEF {
+            if binding != STB_WEAK {
+                symbols.undefined.push(symbol_name);
+            }
+        } else if matches!(binding, STB_GLOBAL | STB_WEAK | STB_GNU_UNIQUE)
+            && matches!(visibility, STV_DEFAULT | STV_PROTECTED)
+        {
+            let _ = symbols.defined.insert(symbol_name);
+        }
+    }
+    Some(symbols)
+}
+
+/// Returns the kernel release a kernel module was built for, which is the first
+/// word of the `vermagic` entry in its `.modinfo` section
+pub fn parse_module_kernel(data: &[u8]) -> Option<String> {
+    let reader = reader(data)?;
+    let modinfo = reader.section(".modinfo")?;
+    modinfo
+        .split(|byte| *byte == 0)
+        .find_map(|entry| entry.strip_prefix(b"vermagic="))
+        .and_then(|vermagic| {
+            String::from_utf8_lossy(vermagic)
+                .split_whitespace()
+                .next()
+                .map(String::from)
+        })
+}
+
+fn split_search_path(path: &str) -> impl Iterator<Item = String> + '_ {
+    path.split(':')
+        .filter(|directory| !directory.is_empty())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A little endian `Elf_Verneed` entry requiring `FOO_1.0` of `libfoo.so.1`,
+    /// an `Elf_Verdef` entry defining `FOO_1.0` and their string table
+    fn version_data(verneed_next: u32, verdef_next: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        // Elf_Verneed: version, count, file, aux, next
+        data.extend(&1_u16.to_le_bytes());
+        data.extend(&1_u16.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&16_u32.to_le_bytes());
+        data.extend(&verneed_next.to_le_bytes());
+        // Elf_Vernaux: hash, flags, other, name, next
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&0_u16.to_le_bytes());
+        data.extend(&2_u16.to_le_bytes());
+        data.extend(&13_u32.to_le_bytes());
+        data.extend(&0_u32.to_le_bytes());
+        // Elf_Verdef: version, flags, index, count, hash, aux, next
+        data.extend(&1_u16.to_le_bytes());
+        data.extend(&0_u16.to_le_bytes());
+        data.extend(&2_u16.to_le_bytes());
+        data.extend(&1_u16.to_le_bytes());
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&20_u32.to_le_bytes());
+        data.extend(&verdef_next.to_le_bytes());
+        // Elf_Verdaux: name, next
+        data.extend(&13_u32.to_le_bytes());
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(b"\0libfoo.so.1\0FOO_1.0\0");
+        data
+    }
+
+    fn reader(data: &[u8]) -> Reader<'_> {
+        Reader {
+            data,
+            class: Class::Elf64,
+            endianness: Endianness::Little,
+        }
+    }
+
+    fn strings(data: &[u8]) -> StringTable {
+        StringTable {
+            offset: 60,
+            end: u64::try_from(data.len()).unwrap(),
+        }
+    }
+
+    #[test]
+    fn version_chains_end_at_a_next_of_zero() {
+        let data = version_data(0, 0);
+        let reader = reader(&data);
+        let requirements = reader
+            .version_requirements(0, 1 << 40, strings(&data))
+            .unwrap();
+        assert_eq!(requirements.len(), 1);
+        assert_eq!(requirements[0].library, "libfoo.so.1");
+        assert_eq!(requirements[0].versions, vec![String::from("FOO_1.0")]);
+        let definitions = reader
+            .version_definitions(32, 1 << 40, strings(&data))
+            .unwrap();
+        assert_eq!(definitions, vec![String::from("FOO_1.0")]);
+    }
+
+    #[test]
+    fn truncated_version_chains_are_rejected() {
+        let data = version_data(1 << 20, 1 << 20);
+        let reader = reader(&data);
+        assert!(reader
+            .version_requirements(0, 1 << 40, strings(&data))
+            .is_none());
+        assert!(reader
+            .version_definitions(32, 1 << 40, strings(&data))
+            .is_none());
+    }
+
+    #[test]
+    fn section_headers_past_the_end_are_rejected() {
+        let mut data = vec![0; 64];
+        data[40..48].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        data[58..60].copy_from_slice(&64_u16.to_le_bytes());
+        data[60..62].copy_from_slice(&1_u16.to_le_bytes());
+        assert!(reader(&data).section(".modinfo").is_none());
+    }
+}
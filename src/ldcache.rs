@@ -0,0 +1,544 @@
+use crate::{
+    elf::{Architecture, Class},
+    root,
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+const OLD_MAGIC: &[u8] = b"ld.so-1.7.0";
+const NEW_MAGIC: &[u8] = b"glibc-ld.so.cache1.1";
+const OLD_HEADER_SIZE: usize = 16;
+const OLD_ENTRY_SIZE: usize = 12;
+const NEW_HEADER_SIZE: usize = 48;
+const NEW_ENTRY_SIZE: usize = 24;
+
+const EXTENSION_MAGIC: u32 = 0xeaa4_2174;
+const EXTENSION_TAG_GLIBC_HWCAPS: u32 = 1;
+/// Set in the hwcap field if the lower 32 bits index the glibc-hwcaps subdirectories
+const HWCAP_EXTENSION: u64 = 1 << 62;
+
+const FLAG_TYPE_MASK: i32 = 0x00ff;
+const FLAG_ELF: i32 = 0x0001;
+const FLAG_ELF_LIBC6: i32 = 0x0003;
+const FLAG_REQUIRED_MASK: i32 = 0xff00;
+
+const EM_MIPS: u16 = 8;
+const EM_PPC64: u16 = 21;
+const EM_S390: u16 = 22;
+const EM_ARM: u16 = 40;
+const EM_SPARCV9: u16 = 43;
+const EM_IA_64: u16 = 50;
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+const EM_RISCV: u16 = 243;
+const EM_LOONGARCH: u16 = 258;
+
+/// The hardware capabilities an entry of the cache requires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HardwareCapabilities {
+    None,
+    /// legacy hwcap bits (`/usr/lib/tls`, `/usr/lib/x86_64`, ...), ignored by current glibc
+    Legacy(u64),
+    /// a `glibc-hwcaps` subdirectory like `x86-64-v3`
+    Subdirectory(String),
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    flags: i32,
+    hardware_capabilities: HardwareCapabilities,
+    path: PathBuf,
+}
+
+/// The content of `/etc/ld.so.cache` as written by `ldconfig`
+#[derive(Debug, Default)]
+pub struct LdCache {
+    entries: HashMap<String, Vec<CacheEntry>>,
+}
+
+/// The directories configured in `/etc/ld.so.conf` and its `include` files
+#[derive(Debug, Default)]
+pub struct LdSoConf {
+    pub directories: Vec<PathBuf>,
+    pub files: Vec<PathBuf>,
+}
+
+impl LdCache {
+    /// Returns the paths of all entries for `library` usable by `architecture`
+    /// in the order `ld.so` tries them
+    pub fn lookup<'b>(
+        &'b self,
+        library: &str,
+        architecture: Architecture,
+    ) -> impl Iterator<Item = &'b Path> + 'b {
+        self.entries
+            .get(library)
+            .into_iter()
+            .flatten()
+            .filter(move |entry| match entry.hardware_capabilities {
+                // the dynamic linker ignores legacy hwcap entries since glibc 2.37
+                HardwareCapabilities::Legacy(_) => false,
+                HardwareCapabilities::None | HardwareCapabilities::Subdirectory(_) => {
+                    matches_architecture(entry.flags, architecture)
+                }
+            })
+            .map(|entry| entry.path.as_path())
+    }
+
+    /// Returns the paths of `library` for every architecture
+    pub fn lookup_any<'b>(&'b self, library: &str) -> impl Iterator<Item = &'b Path> + 'b {
+        self.entries
+            .get(library)
+            .into_iter()
+            .flatten()
+            .map(|entry| entry.path.as_path())
+    }
+
+    /// Checks whether the libraries `ldconfig` found in `directory` are usable by
+    /// `architecture`. Returns `None` if the cache contains no library of the directory
+    pub fn directory_matches_architecture(
+        &self,
+        directory: &Path,
+        architecture: Architecture,
+    ) -> Option<bool> {
+        let mut flags = self
+            .entries
+            .values()
+            .flatten()
+            .filter(|entry| entry.path.parent() == Some(directory))
+            .map(|entry| entry.flags)
+            .peekable();
+        let _ = flags.peek()?;
+        Some(flags.any(|flags| matches_architecture(flags, architecture)))
+    }
+
+    fn insert(&mut self, name: String, entry: CacheEntry) {
+        self.entries.entry(name).or_default().push(entry);
+    }
+}
+
+/// Checks whether the flags `ldconfig` stored for a library allow it to be
+/// loaded by a file of the given architecture
+fn matches_architecture(flags: i32, architecture: Architecture) -> bool {
+    if !(FLAG_ELF..=FLAG_ELF_LIBC6).contains(&(flags & FLAG_TYPE_MASK)) {
+        return false;
+    }
+    let required: &[i32] = match (architecture.class, architecture.machine) {
+        (Class::Elf64, EM_SPARCV9) => &[0x0100],
+        (Class::Elf64, EM_IA_64) => &[0x0200],
+        (Class::Elf64, EM_X86_64) => &[0x0300],
+        (Class::Elf64, EM_S390) => &[0x0400],
+        (Class::Elf64, EM_PPC64) => &[0x0500],
+        (Class::Elf32, EM_X86_64) => &[0x0800],
+        (Class::Elf64, EM_AARCH64) => &[0x0a00],
+        (Class::Elf32, EM_ARM) => &[0x0000, 0x0900, 0x0b00],
+        (_, EM_RISCV) => &[0x0f00, 0x1000],
+        (Class::Elf64, EM_LOONGARCH) => &[0x1100, 0x1200],
+        // the mips abis cannot be distinguished by class and machine alone
+        (_, EM_MIPS) => return true,
+        _ => &[0x0000],
+    };
+    required.contains(&(flags & FLAG_REQUIRED_MASK))
+}
+
+/// Reads the cache at `path`. Returns `None` if there is no cache or
+/// its format is unknown
+pub fn read_ld_so_cache(path: &Path) -> Option<LdCache> {
+    let data = fs::read(path).ok()?;
+    if data.starts_with(NEW_MAGIC) {
+        return parse_new_format(&data, 0);
+    }
+    if !data.starts_with(OLD_MAGIC) {
+        return None;
+    }
+    // a cache in the new format may follow the old one for compatibility
+    let libraries = usize::try_from(read_u32(&data, 12)?).ok()?;
+    let new_offset = libraries
+        .checked_mul(OLD_ENTRY_SIZE)?
+        .checked_add(OLD_HEADER_SIZE + 7)?
+        // the new format is aligned to eight bytes
+        & !7;
+    if data
+        .get(new_offset..)
+        .is_some_and(|new| new.starts_with(NEW_MAGIC))
+    {
+        parse_new_format(&data, new_offset)
+    } else {
+        parse_old_format(&data, libraries)
+    }
+}
+
+/// Parses the format used by glibc before 2.32. String offsets are relative
+/// to the end of the entries
+fn parse_old_format(data: &[u8], libraries: usize) -> Option<LdCache> {
+    let strings = libraries
+        .checked_mul(OLD_ENTRY_SIZE)?
+        .checked_add(OLD_HEADER_SIZE)?;
+    let mut cache = LdCache::default();
+    for index in 0..libraries {
+        let entry = OLD_HEADER_SIZE + index * OLD_ENTRY_SIZE;
+        let flags = read_i32(data, entry)?;
+        let key = read_string(data, strings, read_u32(data, entry + 4)?)?;
+        let value = read_string(data, strings, read_u32(data, entry + 8)?)?;
+        cache.insert(
+            key,
+            CacheEntry {
+                flags,
+                hardware_capabilities: HardwareCapabilities::None,
+                path: PathBuf::from(value),
+            },
+        );
+    }
+    Some(cache)
+}
+
+/// Parses the `glibc-ld.so.cache1.1` format starting at `start`. String
+/// offsets are relative to `start`
+fn parse_new_format(data: &[u8], start: usize) -> Option<LdCache> {
+    let header = data.get(start..)?;
+    let libraries = usize::try_from(read_u32(header, 20)?).ok()?;
+    let endianness = *header.get(28)?;
+    // caches created for a different byte order are ignored by the dynamic linker
+    if (cfg!(target_endian = "little") && endianness == 3)
+        || (cfg!(target_endian = "big") && endianness == 2)
+    {
+        return None;
+    }
+    let subdirectories = read_hwcap_subdirectories(header, read_u32(header, 32)?);
+
+    let mut cache = LdCache::default();
+    for index in 0..libraries {
+        let entry = NEW_HEADER_SIZE.checked_add(index.checked_mul(NEW_ENTRY_SIZE)?)?;
+        let flags = read_i32(header, entry)?;
+        let key = read_string(header, 0, read_u32(header, entry + 4)?)?;
+        let value = read_string(header, 0, read_u32(header, entry + 8)?)?;
+        let hwcap = read_u64(header, entry + 16)?;
+        let hardware_capabilities = if hwcap & HWCAP_EXTENSION != 0 {
+            let index = usize::try_from(hwcap & u64::from(u32::MAX)).ok()?;
+            HardwareCapabilities::Subdirectory(subdirectories.get(index)?.clone())
+        } else if hwcap != 0 {
+            HardwareCapabilities::Legacy(hwcap)
+        } else {
+            HardwareCapabilities::None
+        };
+        cache.insert(
+            key,
+            CacheEntry {
+                flags,
+                hardware_capabilities,
+                path: PathBuf::from(value),
+            },
+        );
+    }
+    Some(cache)
+}
+
+/// Reads the names of the `glibc-hwcaps` subdirectories stored in the
+/// extension sections following the string table
+fn read_hwcap_subdirectories(header: &[u8], extension_offset: u32) -> Vec<String> {
+    let read = || {
+        let extension = usize::try_from(extension_offset).ok()?;
+        if extension == 0 || read_u32(header, extension)? != EXTENSION_MAGIC {
+            return None;
+        }
+        let count = usize::try_from(read_u32(header, extension + 4)?).ok()?;
+        for index in 0..count {
+            let section = extension.checked_add(8 + index.checked_mul(16)?)?;
+            if read_u32(header, section)? != EXTENSION_TAG_GLIBC_HWCAPS {
+                continue;
+            }
+            let offset = usize::try_from(read_u32(header, section + 8)?).ok()?;
+            let size = usize::try_from(read_u32(header, section + 12)?).ok()?;
+            return (0..size / 4)
+                .map(|string| read_string(header, 0, read_u32(header, offset + string * 4)?))
+                .collect();
+        }
+        None
+    };
+    read().unwrap_or_default()
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    <[u8; N]>::try_from(data.get(offset..offset.checked_add(N)?)?).ok()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    read_bytes(data, offset).map(u32::from_ne_bytes)
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Option<i32> {
+    read_bytes(data, offset).map(i32::from_ne_bytes)
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    read_bytes(data, offset).map(u64::from_ne_bytes)
+}
+
+fn read_string(data: &[u8], base: usize, offset: u32) -> Option<String> {
+    let start = base.checked_add(usize::try_from(offset).ok()?)?;
+    let bytes = data.get(start..)?;
+    let length = bytes.iter().position(|byte| *byte == 0)?;
+    Some(String::from_utf8_lossy(bytes.get(..length)?).into_owned())
+}
+
+/// A cache older than the configuration or one of the library directories
+/// may not contain all libraries
+pub fn is_stale(cache: &Path, configuration: &LdSoConf, trusted_directories: &[PathBuf]) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    let Ok(cache_modified) = modified(cache) else {
+        return true;
+    };
+    configuration
+        .files
+        .iter()
+        .chain(&configuration.directories)
+        .chain(trusted_directories)
+        .filter_map(|path| modified(path).ok())
+        .any(|path_modified: SystemTime| path_modified > cache_modified)
+}
+
+/// Reads `/etc/ld.so.conf` and all files included by it. The included files and
+/// the directories are located below `root`
+pub fn read_ld_so_conf(path: &Path, root: &Path) -> LdSoConf {
+    let mut configuration = LdSoConf::default();
+    read_ld_so_conf_file(path, root, &mut configuration);
+    configuration
+}
+
+fn read_ld_so_conf_file(path: &Path, root: &Path, configuration: &mut LdSoConf) {
+    // a missing configuration just means there are no additional directories
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    configuration.files.push(path.to_path_buf());
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(pattern) = line.strip_prefix("include") {
+            for include in expand_glob(pattern.trim(), path, root) {
+                read_ld_so_conf_file(&include, root, configuration);
+            }
+        } else if !line.is_empty() {
+            let directory = root::join(root, Path::new(line));
+            if !configuration.directories.contains(&directory) {
+                configuration.directories.push(directory);
+            }
+        }
+    }
+}
+
+/// Expands the wildcards of the last path component of an `include` statement.
+/// Relative patterns are interpreted relative to the including file
+fn expand_glob(pattern: &str, including_file: &Path, root: &Path) -> Vec<PathBuf> {
+    let is_absolute = Path::new(pattern).is_absolute();
+    let pattern = including_file
+        .parent()
+        .unwrap_or_else(|| Path::new("/"))
+        .join(pattern);
+    let (directory, file_pattern) = match (pattern.parent(), pattern.file_name()) {
+        (Some(directory), Some(file_pattern)) => (directory, file_pattern.to_string_lossy()),
+        _ => return vec![],
+    };
+    // the including file is already located below the root
+    let directory = if is_absolute {
+        root::join(root, directory)
+    } else {
+        directory.to_path_buf()
+    };
+    let pattern = directory.join(&*file_pattern);
+    if !file_pattern.contains(['*', '?']) {
+        return vec![pattern];
+    }
+    let mut files = fs::read_dir(&directory)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .is_some_and(|name| glob_matches(&file_pattern, &name.to_string_lossy()))
+                })
+                .collect::<Vec<PathBuf>>()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    match pattern.chars().next() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = &pattern[1..];
+            name.char_indices()
+                .map(|(index, _)| index)
+                .chain(std::iter::once(name.len()))
+                .any(|index| glob_matches(rest, &name[index..]))
+        }
+        Some(character) => {
+            let mut name_chars = name.chars();
+            match name_chars.next() {
+                Some(name_character) if character == '?' || character == name_character => {
+                    glob_matches(&pattern[character.len_utf8()..], name_chars.as_str())
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X86_64: Architecture = Architecture {
+        class: Class::Elf64,
+        machine: EM_X86_64,
+    };
+    const X86_64_FLAGS: i32 = FLAG_ELF_LIBC6 | 0x0300;
+
+    /// Appends `string` to the string table of `data` and returns its offset
+    fn push_string(data: &mut Vec<u8>, base: usize, string: &str) -> u32 {
+        let offset = u32::try_from(data.len() - base).unwrap();
+        data.extend(string.as_bytes());
+        data.push(0);
+        offset
+    }
+
+    /// A cache in the old format containing `libfoo.so.1` in `/usr/lib`
+    fn old_format() -> Vec<u8> {
+        let mut data = Vec::from(OLD_MAGIC);
+        data.resize(12, 0);
+        data.extend(&1_u32.to_ne_bytes());
+        data.resize(OLD_HEADER_SIZE + OLD_ENTRY_SIZE, 0);
+        let strings = data.len();
+        let key = push_string(&mut data, strings, "libfoo.so.1");
+        let value = push_string(&mut data, strings, "/usr/lib/libfoo.so.1");
+        data[16..20].copy_from_slice(&X86_64_FLAGS.to_ne_bytes());
+        data[20..24].copy_from_slice(&key.to_ne_bytes());
+        data[24..28].copy_from_slice(&value.to_ne_bytes());
+        data
+    }
+
+    /// A cache in the new format containing `libfoo.so.1` in the `x86-64-v3`
+    /// `glibc-hwcaps` subdirectory and in `/usr/lib`
+    fn new_format(endianness: u8) -> Vec<u8> {
+        let mut data = Vec::from(NEW_MAGIC);
+        data.extend(&2_u32.to_ne_bytes());
+        data.extend(&0_u32.to_ne_bytes());
+        data.push(endianness);
+        data.resize(NEW_HEADER_SIZE + 2 * NEW_ENTRY_SIZE, 0);
+        let key = push_string(&mut data, 0, "libfoo.so.1");
+        let hwcaps = push_string(&mut data, 0, "/usr/lib/glibc-hwcaps/x86-64-v3/libfoo.so.1");
+        let plain = push_string(&mut data, 0, "/usr/lib/libfoo.so.1");
+        let subdirectory = push_string(&mut data, 0, "x86-64-v3");
+        for (index, (value, hwcap)) in [(hwcaps, HWCAP_EXTENSION), (plain, 0)].iter().enumerate() {
+            let entry = NEW_HEADER_SIZE + index * NEW_ENTRY_SIZE;
+            data[entry..entry + 4].copy_from_slice(&X86_64_FLAGS.to_ne_bytes());
+            data[entry + 4..entry + 8].copy_from_slice(&key.to_ne_bytes());
+            data[entry + 8..entry + 12].copy_from_slice(&value.to_ne_bytes());
+            data[entry + 16..entry + 24].copy_from_slice(&hwcap.to_ne_bytes());
+        }
+        let extension = u32::try_from(data.len()).unwrap();
+        data[32..36].copy_from_slice(&extension.to_ne_bytes());
+        let section = extension + 24;
+        for value in &[
+            EXTENSION_MAGIC,
+            1,
+            EXTENSION_TAG_GLIBC_HWCAPS,
+            0,
+            section,
+            4,
+        ] {
+            data.extend(&value.to_ne_bytes());
+        }
+        data.extend(&subdirectory.to_ne_bytes());
+        data
+    }
+
+    fn native_endianness() -> u8 {
+        if cfg!(target_endian = "little") {
+            2
+        } else {
+            3
+        }
+    }
+
+    #[test]
+    fn old_format_is_parsed() {
+        let cache = parse_old_format(&old_format(), 1).unwrap();
+        assert_eq!(
+            cache.lookup("libfoo.so.1", X86_64).collect::<Vec<&Path>>(),
+            vec![Path::new("/usr/lib/libfoo.so.1")]
+        );
+        assert_eq!(cache.lookup("libbar.so.1", X86_64).count(), 0);
+    }
+
+    #[test]
+    fn new_format_reads_hwcap_subdirectories() {
+        let cache = parse_new_format(&new_format(native_endianness()), 0).unwrap();
+        let entries = &cache.entries["libfoo.so.1"];
+        assert_eq!(
+            entries[0].hardware_capabilities,
+            HardwareCapabilities::Subdirectory(String::from("x86-64-v3"))
+        );
+        assert_eq!(entries[1].hardware_capabilities, HardwareCapabilities::None);
+        assert_eq!(cache.lookup("libfoo.so.1", X86_64).count(), 2);
+    }
+
+    #[test]
+    fn caches_of_another_byte_order_are_ignored() {
+        let wrong = if native_endianness() == 2 { 3 } else { 2 };
+        assert!(parse_new_format(&new_format(wrong), 0).is_none());
+    }
+
+    #[test]
+    fn truncated_hwcap_extensions_are_ignored() {
+        let mut data = new_format(native_endianness());
+        data.truncate(data.len() - 4);
+        assert!(read_hwcap_subdirectories(&data, read_u32(&data, 32).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn globs_match_like_the_shell() {
+        assert!(glob_matches("*.conf", "fakeroot.conf"));
+        assert!(glob_matches("*.conf", ".conf"));
+        assert!(!glob_matches("*.conf", "fakeroot.conf.bak"));
+        assert!(glob_matches("lib?.conf", "lib3.conf"));
+        assert!(!glob_matches("lib?.conf", "lib.conf"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn includes_are_expanded() {
+        let directory = std::env::temp_dir().join(format!("ldcache-test-{}", std::process::id()));
+        fs::create_dir_all(directory.join("ld.so.conf.d")).unwrap();
+        fs::write(
+            directory.join("ld.so.conf"),
+            "/usr/lib/first # comment\ninclude ld.so.conf.d/*.conf\n",
+        )
+        .unwrap();
+        fs::write(directory.join("ld.so.conf.d/b.conf"), "/usr/lib/b\n").unwrap();
+        fs::write(
+            directory.join("ld.so.conf.d/a.conf"),
+            "/usr/lib/a\n/usr/lib/b\n",
+        )
+        .unwrap();
+        fs::write(directory.join("ld.so.conf.d/c.conf.bak"), "/usr/lib/c\n").unwrap();
+        let configuration = read_ld_so_conf(&directory.join("ld.so.conf"), Path::new("/"));
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(
+            configuration.directories,
+            vec![
+                PathBuf::from("/usr/lib/first"),
+                PathBuf::from("/usr/lib/a"),
+                PathBuf::from("/usr/lib/b"),
+            ]
+        );
+        assert_eq!(configuration.files.len(), 3);
+    }
+}
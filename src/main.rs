@@ -1,8 +1,8 @@
 //! A program for checking `ArchLinux` packages for missing libraries.
 //!
 //! If a package is missing a library it may mean, that is necessary to rebuild that given package.
-//! This binary checks every elf file in a package using either ldd or readelf and reports missing
-//! libraries.
+//! This binary checks every elf file in a package by emulating the library search of the
+//! dynamic linker and reports missing libraries. No code of the checked files is executed.
 
 // enable additional rustc warnings
 #![warn(
@@ -60,27 +60,33 @@
     clippy::print_stdout
 )]
 
+mod archive;
+mod cache;
 mod cli;
-mod cmd;
 mod data;
+mod database;
+mod elf;
+mod ldcache;
 mod output;
 mod process;
+mod resolve;
+mod root;
+mod runtime;
 
-use crate::{cli::Command, data::Error};
+use crate::data::Error;
 use std::process::exit;
 
 fn main() {
     let mut settings = handle_error(cli::get_command_line_settings(), 2);
-    handle_error(cmd::check_required_programs(&settings), 3);
-
-    // TODO: Implement readelf and remove following lines
-    if let Command::Readelf = settings.command {
-        println!("readelf is currently not supported but will be added shortly");
-        exit(10);
-    }
 
+    let database = handle_error(
+        database::LocalDatabase::read(&settings.db_path, &settings.root),
+        6,
+    );
     if settings.all_packages {
-        handle_error(cmd::get_all_packages(&mut settings), 4);
+        let sync_databases = handle_error(database::SyncDatabase::read_all(&settings.db_path), 4);
+        settings.packages =
+            database::get_foreign_packages(&database, &sync_databases, settings.include_outdated);
     }
     // TODO: Replace with verbose
     // TODO: Print more information with verbose like the file types which are checked
@@ -92,15 +98,18 @@ fn main() {
             }
             print!("{}", package);
         }
+        for (index, path) in settings.paths.iter().chain(&settings.archives).enumerate() {
+            if index != 0 || !settings.packages.is_empty() {
+                print!(", ");
+            }
+            print!("{}", path.to_string_lossy());
+        }
         println!();
     }
 
-    let packages = handle_error(process::verify_packages(&settings), 5);
+    let packages = handle_error(process::verify_packages(&settings, &database), 5);
     output::print_packages(&packages, &settings);
-    if packages
-        .iter()
-        .any(|package| !package.file_dependencies.is_empty())
-    {
+    if packages.iter().any(data::Package::has_issues) {
         exit(1)
     } else {
         exit(0)
@@ -1,6 +1,6 @@
 use crate::{
     cli::{CommandLineSettings, Output},
-    data::Package,
+    data::{FileDependency, Package},
 };
 use json;
 
@@ -17,23 +17,29 @@ fn print_console(packages: &[Package], settings: &CommandLineSettings) {
             println!();
         }
         println!("========================================");
-        println!("Package: {}", package.name);
+        match package.version {
+            Some(ref version) => println!("Package: {} {}", package.name, version),
+            None => println!("Package: {}", package.name),
+        }
         println!("========================================");
+        print_console_runtime_dependencies(package);
+        print_console_search_path_issues(package);
         if settings.group_by_file {
-            package.file_dependencies.iter().for_each(|dependency| {
-                println!("\nelf file \"{}\" is missing:", dependency.file_name);
-                dependency.library_dependencies.iter().for_each(|library| {
-                    println!("\t{}", library);
-                })
-            });
+            // plugins are listed after the files loaded by the dynamic linker itself
+            package
+                .file_dependencies
+                .iter()
+                .filter(|dependency| !dependency.plugin)
+                .chain(
+                    package
+                        .file_dependencies
+                        .iter()
+                        .filter(|dependency| dependency.plugin),
+                )
+                .for_each(print_console_file_dependency);
         }
         if settings.group_by_library {
-            package.library_requirements.iter().for_each(|library| {
-                println!("\nlibrary \"{}\" is required by:", library.library_name);
-                library.files_requiring.iter().for_each(|file| {
-                    println!("\t{}", file);
-                })
-            });
+            print_console_library_requirements(package);
         }
         if settings.group_by_containing_package {
             package
@@ -47,25 +53,229 @@ fn print_console(packages: &[Package], settings: &CommandLineSettings) {
                     package_entry
                         .packages_containing
                         .iter()
-                        .for_each(|package| {
-                            println!("\t{}", package);
+                        .for_each(|candidate| {
+                            let file = &candidate.file;
+                            print!("\t{}/{}: {}", file.repository, file.package, file.path);
+                            if !candidate.on_linker_path {
+                                print!(" (not on linker path)");
+                            }
+                            if !candidate.architecture_matches {
+                                print!(" (wrong architecture)");
+                            }
+                            println!();
                         })
                 });
         }
     }
 }
 
+fn print_console_file_dependency(dependency: &FileDependency) {
+    let kind = if dependency.plugin {
+        "plugin"
+    } else {
+        "elf file"
+    };
+    if !dependency.library_dependencies.is_empty() {
+        println!("\n{} \"{}\" is missing:", kind, dependency.file_name);
+        dependency.library_dependencies.iter().for_each(|library| {
+            println!("\t{}", library);
+        });
+    }
+    if !dependency.version_dependencies.is_empty() {
+        println!(
+            "\n{} \"{}\" is missing symbol versions:",
+            kind, dependency.file_name
+        );
+        dependency.version_dependencies.iter().for_each(|missing| {
+            println!("\t{} ({})", missing.version, missing.library);
+        });
+    }
+    if !dependency.symbol_dependencies.is_empty() {
+        println!(
+            "\n{} \"{}\" has undefined symbols:",
+            kind, dependency.file_name
+        );
+        dependency.symbol_dependencies.iter().for_each(|symbol| {
+            println!("\t{}", symbol);
+        });
+    }
+    if !dependency.unused_libraries.is_empty() {
+        println!("\n{} \"{}\" uses no symbol of:", kind, dependency.file_name);
+        dependency.unused_libraries.iter().for_each(|library| {
+            println!("\t{}", library);
+        });
+    }
+    if !dependency.underlinked_symbols.is_empty() {
+        println!(
+            "\n{} \"{}\" uses symbols of indirectly loaded libraries:",
+            kind, dependency.file_name
+        );
+        dependency
+            .underlinked_symbols
+            .iter()
+            .for_each(|underlinked| {
+                println!("\t{} ({})", underlinked.symbol, underlinked.library);
+            });
+    }
+    if !dependency.undeclared_dependencies.is_empty() {
+        println!(
+            "\n{} \"{}\" uses libraries of undeclared dependencies:",
+            kind, dependency.file_name
+        );
+        dependency
+            .undeclared_dependencies
+            .iter()
+            .for_each(|undeclared| {
+                println!(
+                    "\t{}: {} ({})",
+                    undeclared.library, undeclared.path, undeclared.package
+                );
+            });
+    }
+    if !dependency.architecture_mismatches.is_empty() {
+        println!(
+            "\n{} \"{}\" ({}) only found libraries of another architecture:",
+            kind, dependency.file_name, dependency.architecture
+        );
+        dependency
+            .architecture_mismatches
+            .iter()
+            .for_each(|mismatch| {
+                println!(
+                    "\t{}: {} ({})",
+                    mismatch.library, mismatch.path, mismatch.architecture
+                );
+            });
+    }
+    if !dependency.fragile_dependencies.is_empty() {
+        println!(
+            "\n{} \"{}\" only finds libraries outside of the linker path:",
+            kind, dependency.file_name
+        );
+        dependency.fragile_dependencies.iter().for_each(|fragile| {
+            println!(
+                "\t{}: {} ({})",
+                fragile.library, fragile.path, fragile.reason
+            );
+        });
+    }
+}
+
+fn print_console_library_requirements(package: &Package) {
+    package.library_requirements.iter().for_each(|library| {
+        println!("\nlibrary \"{}\" is required by:", library.library_name);
+        library.files_requiring.iter().for_each(|file| {
+            println!("\t{}", file);
+        })
+    });
+    package.haskell_requirements.iter().for_each(|requirement| {
+        let installed = if requirement.installed_versions.is_empty() {
+            String::from("none")
+        } else {
+            requirement.installed_versions.join(", ")
+        };
+        println!(
+            "\nhaskell libraries built with ghc {} (installed: {}) are missing:",
+            requirement.ghc_version, installed
+        );
+        requirement.libraries.iter().for_each(|library| {
+            println!("\t{}", library);
+        });
+        println!(
+            "\nhaskell libraries built with ghc {} are required by:",
+            requirement.ghc_version
+        );
+        requirement.files_requiring.iter().for_each(|file| {
+            println!("\t{}", file);
+        });
+    });
+    package.version_requirements.iter().for_each(|requirement| {
+        println!(
+            "\nversion \"{}\" of library \"{}\" is required by:",
+            requirement.version.version, requirement.version.library
+        );
+        requirement.files_requiring.iter().for_each(|file| {
+            println!("\t{}", file);
+        });
+    });
+    package.symbol_requirements.iter().for_each(|symbol| {
+        println!("\nsymbol \"{}\" is required by:", symbol.symbol_name);
+        symbol.files_requiring.iter().for_each(|file| {
+            println!("\t{}", file);
+        });
+    });
+    package.library_replacements.iter().for_each(|replacement| {
+        println!(
+            "\nlibrary \"{}\" might have been replaced by:",
+            replacement.library_name
+        );
+        replacement.replaced_by.iter().for_each(|installed| {
+            println!("\t{}: {}", installed.package, installed.library);
+        });
+    });
+}
+
+fn print_console_runtime_dependencies(package: &Package) {
+    package.runtime_dependencies.iter().for_each(|runtime| {
+        match runtime.installed_version {
+            Some(ref installed_version) => println!(
+                "\nfiles for {} {} (installed: {}):",
+                runtime.runtime, runtime.version, installed_version
+            ),
+            None => println!(
+                "\nfiles for {} {} (not installed):",
+                runtime.runtime, runtime.version
+            ),
+        }
+        runtime.files.iter().for_each(|file| {
+            println!("\t{}", file);
+        });
+    });
+}
+
+fn print_console_search_path_issues(package: &Package) {
+    package
+        .file_dependencies
+        .iter()
+        .filter(|dependency| !dependency.search_path_issues.is_empty())
+        .for_each(|dependency| {
+            println!(
+                "\nsearch paths of \"{}\" are not portable:",
+                dependency.file_name
+            );
+            dependency.search_path_issues.iter().for_each(|issue| {
+                println!("\t{} \"{}\" {}", issue.tag, issue.entry, issue.problem);
+            });
+        });
+}
+
 #[allow(clippy::indexing_slicing)]
 fn print_json(packages: &[Package], settings: &CommandLineSettings) {
     let mut json_packages = json::JsonValue::new_array();
     for package in packages.iter() {
         let mut json_package = json::JsonValue::new_object();
         json_package["package_name"] = package.name.clone().into();
+        if let Some(ref version) = package.version {
+            json_package["version"] = version.clone().into();
+        }
+        if let Some(install_reason) = package.install_reason {
+            json_package["install_reason"] = install_reason.to_string().into();
+            json_package["validation"] = package.validation.clone().into();
+        }
+        json_package["runtime_dependencies"] = print_json_runtime_dependencies(package);
+        json_package["search_path_issues"] = print_json_search_path_issues(package);
         if settings.group_by_file {
-            json_package["file_dependencies"] = print_json_file_dependencies(package);
+            json_package["file_dependencies"] = print_json_file_dependencies(package, false);
+            if settings.plugins {
+                json_package["plugin_dependencies"] = print_json_file_dependencies(package, true);
+            }
         }
         if settings.group_by_library {
             json_package["library_requirements"] = print_json_library_requirements(package);
+            json_package["haskell_requirements"] = print_json_haskell_requirements(package);
+            json_package["version_requirements"] = print_json_version_requirements(package);
+            json_package["symbol_requirements"] = print_json_symbol_requirements(package);
+            json_package["library_replacements"] = print_json_library_replacements(package);
         }
         if settings.group_by_containing_package {
             json_package["packages_containing"] = print_json_packages_containing(package);
@@ -78,22 +288,138 @@ fn print_json(packages: &[Package], settings: &CommandLineSettings) {
 }
 
 #[allow(clippy::indexing_slicing)]
-fn print_json_file_dependencies(package: &Package) -> json::JsonValue {
-    let mut json_file_dependencies = json::JsonValue::new_array();
+fn print_json_runtime_dependencies(package: &Package) -> json::JsonValue {
+    let mut json_runtime_dependencies = json::JsonValue::new_array();
+    package.runtime_dependencies.iter().for_each(|runtime| {
+        let mut json_runtime_dependency = json::JsonValue::new_object();
+        json_runtime_dependency["runtime"] = runtime.runtime.clone().into();
+        json_runtime_dependency["version"] = runtime.version.clone().into();
+        json_runtime_dependency["installed_version"] = runtime.installed_version.clone().into();
+        json_runtime_dependency["files"] = runtime.files.clone().into();
+        json_runtime_dependencies
+            .push(json_runtime_dependency)
+            .expect("Type should be an array");
+    });
+    json_runtime_dependencies
+}
+
+#[allow(clippy::indexing_slicing)]
+fn print_json_search_path_issues(package: &Package) -> json::JsonValue {
+    let mut json_search_path_issues = json::JsonValue::new_array();
     package.file_dependencies.iter().for_each(|dependency| {
-        let mut json_file_dependency = json::JsonValue::new_object();
-        json_file_dependency["file_name"] = (*dependency.file_name).clone().into();
-        let mut json_file_dependencies_array = json::JsonValue::new_array();
-        dependency.library_dependencies.iter().for_each(|library| {
-            json_file_dependencies_array
-                .push((**library).clone())
+        dependency.search_path_issues.iter().for_each(|issue| {
+            let mut json_search_path_issue = json::JsonValue::new_object();
+            json_search_path_issue["file_name"] = (*dependency.file_name).clone().into();
+            json_search_path_issue["tag"] = issue.tag.into();
+            json_search_path_issue["entry"] = issue.entry.clone().into();
+            json_search_path_issue["problem"] = issue.problem.to_string().into();
+            json_search_path_issues
+                .push(json_search_path_issue)
                 .expect("Type should be an array");
         });
-        json_file_dependency["library_dependencies"] = json_file_dependencies_array;
-        json_file_dependencies
-            .push(json_file_dependency)
-            .expect("Type should be an array");
     });
+    json_search_path_issues
+}
+
+#[allow(clippy::indexing_slicing)]
+fn print_json_file_dependencies(package: &Package, plugins: bool) -> json::JsonValue {
+    let mut json_file_dependencies = json::JsonValue::new_array();
+    package
+        .file_dependencies
+        .iter()
+        .filter(|dependency| dependency.plugin == plugins)
+        .for_each(|dependency| {
+            let mut json_file_dependency = json::JsonValue::new_object();
+            json_file_dependency["file_name"] = (*dependency.file_name).clone().into();
+            let mut json_file_dependencies_array = json::JsonValue::new_array();
+            dependency.library_dependencies.iter().for_each(|library| {
+                json_file_dependencies_array
+                    .push((**library).clone())
+                    .expect("Type should be an array");
+            });
+            json_file_dependency["library_dependencies"] = json_file_dependencies_array;
+            let mut json_version_dependencies_array = json::JsonValue::new_array();
+            dependency.version_dependencies.iter().for_each(|missing| {
+                let mut json_version_dependency = json::JsonValue::new_object();
+                json_version_dependency["library"] = missing.library.clone().into();
+                json_version_dependency["version"] = missing.version.clone().into();
+                json_version_dependencies_array
+                    .push(json_version_dependency)
+                    .expect("Type should be an array");
+            });
+            json_file_dependency["version_dependencies"] = json_version_dependencies_array;
+            let mut json_symbol_dependencies_array = json::JsonValue::new_array();
+            dependency.symbol_dependencies.iter().for_each(|symbol| {
+                json_symbol_dependencies_array
+                    .push((**symbol).clone())
+                    .expect("Type should be an array");
+            });
+            json_file_dependency["symbol_dependencies"] = json_symbol_dependencies_array;
+            let mut json_unused_libraries_array = json::JsonValue::new_array();
+            dependency.unused_libraries.iter().for_each(|library| {
+                json_unused_libraries_array
+                    .push(library.clone())
+                    .expect("Type should be an array");
+            });
+            json_file_dependency["unused_libraries"] = json_unused_libraries_array;
+            let mut json_underlinked_symbols_array = json::JsonValue::new_array();
+            dependency
+                .underlinked_symbols
+                .iter()
+                .for_each(|underlinked| {
+                    let mut json_underlinked_symbol = json::JsonValue::new_object();
+                    json_underlinked_symbol["symbol"] = underlinked.symbol.clone().into();
+                    json_underlinked_symbol["library"] = underlinked.library.clone().into();
+                    json_underlinked_symbols_array
+                        .push(json_underlinked_symbol)
+                        .expect("Type should be an array");
+                });
+            json_file_dependency["underlinked_symbols"] = json_underlinked_symbols_array;
+            let mut json_undeclared_dependencies_array = json::JsonValue::new_array();
+            dependency
+                .undeclared_dependencies
+                .iter()
+                .for_each(|undeclared| {
+                    let mut json_undeclared_dependency = json::JsonValue::new_object();
+                    json_undeclared_dependency["library"] = undeclared.library.clone().into();
+                    json_undeclared_dependency["path"] = undeclared.path.clone().into();
+                    json_undeclared_dependency["package"] = undeclared.package.clone().into();
+                    json_undeclared_dependencies_array
+                        .push(json_undeclared_dependency)
+                        .expect("Type should be an array");
+                });
+            json_file_dependency["undeclared_dependencies"] = json_undeclared_dependencies_array;
+            json_file_dependency["architecture"] = dependency.architecture.to_string().into();
+            let mut json_architecture_mismatches_array = json::JsonValue::new_array();
+            dependency
+                .architecture_mismatches
+                .iter()
+                .for_each(|mismatch| {
+                    let mut json_architecture_mismatch = json::JsonValue::new_object();
+                    json_architecture_mismatch["library"] = mismatch.library.clone().into();
+                    json_architecture_mismatch["path"] = mismatch.path.clone().into();
+                    json_architecture_mismatch["architecture"] =
+                        mismatch.architecture.to_string().into();
+                    json_architecture_mismatches_array
+                        .push(json_architecture_mismatch)
+                        .expect("Type should be an array");
+                });
+            json_file_dependency["architecture_mismatches"] = json_architecture_mismatches_array;
+            let mut json_fragile_dependencies_array = json::JsonValue::new_array();
+            dependency.fragile_dependencies.iter().for_each(|fragile| {
+                let mut json_fragile_dependency = json::JsonValue::new_object();
+                json_fragile_dependency["library"] = fragile.library.clone().into();
+                json_fragile_dependency["path"] = fragile.path.clone().into();
+                json_fragile_dependency["reason"] = fragile.reason.to_string().into();
+                json_fragile_dependencies_array
+                    .push(json_fragile_dependency)
+                    .expect("Type should be an array");
+            });
+            json_file_dependency["fragile_dependencies"] = json_fragile_dependencies_array;
+            json_file_dependencies
+                .push(json_file_dependency)
+                .expect("Type should be an array");
+        });
     json_file_dependencies
 }
 
@@ -117,6 +443,93 @@ fn print_json_library_requirements(package: &Package) -> json::JsonValue {
     json_library_requirements
 }
 
+#[allow(clippy::indexing_slicing)]
+fn print_json_haskell_requirements(package: &Package) -> json::JsonValue {
+    let mut json_haskell_requirements = json::JsonValue::new_array();
+    package.haskell_requirements.iter().for_each(|requirement| {
+        let mut json_haskell_requirement = json::JsonValue::new_object();
+        json_haskell_requirement["ghc_version"] = requirement.ghc_version.clone().into();
+        json_haskell_requirement["installed_versions"] =
+            requirement.installed_versions.clone().into();
+        json_haskell_requirement["libraries"] = requirement.libraries.clone().into();
+        let mut json_files_requiring_array = json::JsonValue::new_array();
+        requirement.files_requiring.iter().for_each(|file| {
+            json_files_requiring_array
+                .push((**file).clone())
+                .expect("Type should be an array");
+        });
+        json_haskell_requirement["files_requiring"] = json_files_requiring_array;
+        json_haskell_requirements
+            .push(json_haskell_requirement)
+            .expect("Type should be an array");
+    });
+    json_haskell_requirements
+}
+
+#[allow(clippy::indexing_slicing)]
+fn print_json_version_requirements(package: &Package) -> json::JsonValue {
+    let mut json_version_requirements = json::JsonValue::new_array();
+    package.version_requirements.iter().for_each(|requirement| {
+        let mut json_version_requirement = json::JsonValue::new_object();
+        json_version_requirement["library_name"] = requirement.version.library.clone().into();
+        json_version_requirement["version"] = requirement.version.version.clone().into();
+        let mut json_files_requiring_array = json::JsonValue::new_array();
+        requirement.files_requiring.iter().for_each(|file| {
+            json_files_requiring_array
+                .push((**file).clone())
+                .expect("Type should be an array");
+        });
+        json_version_requirement["files_requiring"] = json_files_requiring_array;
+        json_version_requirements
+            .push(json_version_requirement)
+            .expect("Type should be an array");
+    });
+    json_version_requirements
+}
+
+#[allow(clippy::indexing_slicing)]
+fn print_json_symbol_requirements(package: &Package) -> json::JsonValue {
+    let mut json_symbol_requirements = json::JsonValue::new_array();
+    package.symbol_requirements.iter().for_each(|symbol| {
+        let mut json_symbol_requirement = json::JsonValue::new_object();
+        json_symbol_requirement["symbol_name"] = (*symbol.symbol_name).clone().into();
+        let mut json_files_requiring_array = json::JsonValue::new_array();
+        symbol.files_requiring.iter().for_each(|file| {
+            json_files_requiring_array
+                .push((**file).clone())
+                .expect("Type should be an array");
+        });
+        json_symbol_requirement["files_requiring"] = json_files_requiring_array;
+        json_symbol_requirements
+            .push(json_symbol_requirement)
+            .expect("Type should be an array");
+    });
+    json_symbol_requirements
+}
+
+#[allow(clippy::indexing_slicing)]
+fn print_json_library_replacements(package: &Package) -> json::JsonValue {
+    let mut json_library_replacements = json::JsonValue::new_array();
+    package.library_replacements.iter().for_each(|replacement| {
+        let mut json_library_replacement = json::JsonValue::new_object();
+        json_library_replacement["library_name"] = (*replacement.library_name).clone().into();
+        let mut json_replaced_by_array = json::JsonValue::new_array();
+        replacement.replaced_by.iter().for_each(|installed| {
+            let mut json_installed = json::JsonValue::new_object();
+            json_installed["package"] = installed.package.clone().into();
+            json_installed["library"] = installed.library.clone().into();
+            json_replaced_by_array
+                .push(json_installed)
+                .expect("Type should be an array");
+        });
+        json_library_replacement["replaced_by"] = json_replaced_by_array;
+        json_library_replacements
+            .push(json_library_replacement)
+            .expect("Type should be an array");
+    });
+    json_library_replacements
+}
+
 #[allow(clippy::indexing_slicing)]
 fn print_json_packages_containing(package: &Package) -> json::JsonValue {
     let mut json_packages_containing = json::JsonValue::new_array();
@@ -130,9 +543,15 @@ fn print_json_packages_containing(package: &Package) -> json::JsonValue {
             package_entry
                 .packages_containing
                 .iter()
-                .for_each(|package| {
+                .for_each(|candidate| {
+                    let mut json_package = json::JsonValue::new_object();
+                    json_package["repository"] = candidate.file.repository.clone().into();
+                    json_package["package"] = candidate.file.package.clone().into();
+                    json_package["path"] = candidate.file.path.clone().into();
+                    json_package["on_linker_path"] = candidate.on_linker_path.into();
+                    json_package["architecture_matches"] = candidate.architecture_matches.into();
                     json_packages_containing_array
-                        .push(package.clone())
+                        .push(json_package)
                         .expect("Type should be an array")
                 });
             json_package_containing["packages_containing"] = json_packages_containing_array;
@@ -1,50 +1,389 @@
 use crate::{
-    cli::{Command, CommandLineSettings},
-    cmd,
+    archive,
+    cache::{CachedFile, FileKey, ScanCache},
+    cli::CommandLineSettings,
     data::{
-        Error, LibraryRequired, Package, PackagesContaining, ProcessingFileDependency,
-        ProcessingPackage,
+        Error, FragileLibrary, Fragility, HaskellRequired, LibraryReplacement, LibraryRequired,
+        MissingVersion, Package, PackageCandidate, PackagesContaining, ProcessingFileDependency,
+        ProcessingPackage, SearchPathIssue, SearchPathProblem, SymbolRequired,
+        UndeclaredDependency, UnderlinkedSymbol, VersionRequired,
     },
+    database::{self, FileIndex, LibraryIndex, LocalDatabase, OwnerIndex},
+    elf::{self, Architecture, ElfFile, Symbols},
+    resolve::{Resolution, Resolver, SearchSource, SearchedLibrary},
+    runtime::{self, HaskellLibrary, Runtimes},
 };
 use rayon::prelude::*;
-use std::{collections::HashMap, path::PathBuf, rc::Rc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+
+/// Directories packages are built in, which do not exist on the systems they are installed on
+const BUILD_DIRECTORIES: [&str; 5] = ["/build/", "/home/", "/root/", "/tmp/", "/var/tmp/"];
+
+/// The packages whose libraries a package may load
+struct Declared<'b> {
+    owners: &'b OwnerIndex,
+    /// the package itself and its dependencies
+    packages: HashSet<String>,
+}
+
+impl<'b> Declared<'b> {
+    fn new(
+        owners: &'b OwnerIndex,
+        database: &LocalDatabase,
+        name: &str,
+        depends: &[String],
+    ) -> Self {
+        let mut packages = database.dependency_closure(depends);
+        let _ = packages.insert(String::from(name));
+        Self { owners, packages }
+    }
+}
 
-pub fn verify_packages(settings: &CommandLineSettings) -> Result<Vec<Package>, Error<'_>> {
+/// A loadable elf file of a package archive
+struct ArchiveFile {
+    /// the location the file is installed to
+    file: String,
+    elf: Arc<ElfFile>,
+    /// only read for the deep verification
+    symbols: Arc<Symbols>,
+}
+
+pub fn verify_packages<'a>(
+    settings: &CommandLineSettings,
+    database: &LocalDatabase,
+) -> Result<Vec<Package>, Error<'a>> {
+    let resolver = Resolver::new(&settings.root, settings.use_environment);
+    let runtimes = Runtimes::detect(&settings.root)?;
+    let scan_cache = if settings.no_cache {
+        None
+    } else {
+        let options = format!("{} {}", settings.root.display(), settings.use_environment);
+        ScanCache::read(&resolver.state_paths(), &options)
+    };
+    let owners = if settings.undeclared {
+        Some(OwnerIndex::read(database)?)
+    } else {
+        None
+    };
+    // files below the paths are only checked if no package owns them
+    let owned = if settings.paths.is_empty() {
+        HashSet::new()
+    } else {
+        database.owned_files()?
+    };
     let mut packages = settings
         .packages
         .par_iter()
-        .map(|package| verify_package(package, settings))
+        .map(|package| {
+            verify_package(
+                package,
+                database,
+                owners.as_ref(),
+                &resolver,
+                &runtimes,
+                scan_cache.as_ref(),
+                settings,
+            )
+        })
+        .chain(settings.archives.par_iter().map(|archive| {
+            verify_archive(
+                archive,
+                database,
+                owners.as_ref(),
+                &resolver,
+                &runtimes,
+                settings,
+            )
+        }))
+        .chain(settings.paths.par_iter().map(|path| {
+            verify_path(
+                path,
+                &owned,
+                &resolver,
+                &runtimes,
+                scan_cache.as_ref(),
+                settings,
+            )
+        }))
         .collect::<Result<Vec<ProcessingPackage>, Error<'_>>>()?
         .into_iter()
         .map(|package| package.into())
         .collect::<Vec<Package>>();
+    // the check itself succeeded, a read-only cache directory only slows down the next run
+    if let Some(Err(error)) = scan_cache.as_ref().map(ScanCache::write) {
+        if !settings.quite {
+            println!("Could not write the scan cache: {}", error);
+        }
+    }
 
+    let ghc_versions = runtime::installed_ghc_versions(&settings.root);
     let _ = packages
         .iter_mut()
-        .map(|mut package| {
-            setup_library_requirements(&mut package)?;
-            if settings.show_candidates {
-                setup_packages_containing(&mut package)?;
-            }
-            Ok(())
-        })
+        .map(|mut package| setup_library_requirements(&mut package, &ghc_versions))
         .collect::<Result<Vec<_>, Error<'_>>>()?;
+    for package in &mut packages {
+        setup_version_requirements(package);
+        setup_symbol_requirements(package);
+    }
+    // the file lists of installed packages are only read if libraries are missing
+    let stems = packages
+        .iter()
+        .flat_map(|package| package.library_requirements.iter())
+        .filter_map(|library| database::library_stem(&library.library_name))
+        .map(String::from)
+        .collect::<HashSet<String>>();
+    if !stems.is_empty() {
+        let library_index = LibraryIndex::read(database, &stems)?;
+        for package in &mut packages {
+            setup_library_replacements(package, &library_index);
+        }
+    }
+    if settings.show_candidates {
+        // the files databases are read only once for the libraries of all packages
+        let libraries = packages
+            .iter()
+            .flat_map(|package| package.library_requirements.iter())
+            .map(|library| (*library.library_name).clone())
+            .collect::<HashSet<String>>();
+        let file_index = FileIndex::read(&settings.db_path, &libraries)?;
+        for package in &mut packages {
+            setup_packages_containing(package, &file_index, &resolver);
+        }
+    }
 
     Ok(packages)
 }
 
 fn verify_package<'a>(
     package_name: &str,
+    database: &LocalDatabase,
+    owners: Option<&OwnerIndex>,
+    resolver: &Resolver,
+    runtimes: &Runtimes,
+    scan_cache: Option<&ScanCache>,
+    settings: &CommandLineSettings,
+) -> Result<ProcessingPackage, Error<'a>> {
+    let local_package = database.package(package_name)?;
+    let files = database.files(local_package)?;
+    let package = ProcessingPackage {
+        version: Some(local_package.version.clone()),
+        install_reason: Some(local_package.reason),
+        validation: local_package.validation.clone(),
+        ..ProcessingPackage::new(package_name)
+    };
+    let declared =
+        owners.map(|owners| Declared::new(owners, database, package_name, &local_package.depends));
+    verify_files(
+        package,
+        &files,
+        resolver,
+        runtimes,
+        scan_cache,
+        declared.as_ref(),
+        settings,
+    )
+}
+
+/// Checks the files below `path` which are not part of any package. They are
+/// reported as a package named after `path`
+fn verify_path<'a>(
+    path: &Path,
+    owned: &HashSet<String>,
+    resolver: &Resolver,
+    runtimes: &Runtimes,
+    scan_cache: Option<&ScanCache>,
     settings: &CommandLineSettings,
 ) -> Result<ProcessingPackage, Error<'a>> {
-    let files = cmd::get_files_for_package(package_name)?;
-    let filenames = get_filenames_from_files(&files);
-    let mut package = ProcessingPackage::new(package_name);
+    let root = fs::canonicalize(resolver.locate(path))
+        .map_err(|_| Error::PathNotFound(path.to_string_lossy().into_owned()))?;
+    let mut files = Vec::new();
+    walk_directory(&root, owned, &mut files)?;
+    let package = ProcessingPackage::new(root.to_string_lossy());
+    verify_files(
+        package, &files, resolver, runtimes, scan_cache, None, settings,
+    )
+}
+
+/// Collects the files below `path` which are not in `owned`. Symlinks to
+/// directories are not followed
+fn walk_directory<'a>(
+    path: &Path,
+    owned: &HashSet<String>,
+    files: &mut Vec<String>,
+) -> Result<(), Error<'a>> {
+    if !fs::symlink_metadata(path)?.is_dir() {
+        let file = path.to_string_lossy().into_owned();
+        if !owned.contains(&file) {
+            files.push(file);
+        }
+        return Ok(());
+    }
+    let mut entries = match fs::read_dir(path) {
+        Ok(entries) => entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, io::Error>>()?,
+        // paths like /opt contain private directories of other users, which are not
+        // checkable without their permissions and should not abort the whole walk
+        Err(ref error) if error.kind() == ErrorKind::PermissionDenied => return Ok(()),
+        Err(error) => return Err(error.into()),
+    };
+    entries.sort();
+    for entry in entries {
+        walk_directory(&entry, owned, files)?;
+    }
+    Ok(())
+}
+
+/// Checks the files of the package archive at `path` as if they were installed.
+/// The archive is not extracted
+fn verify_archive<'a>(
+    path: &Path,
+    database: &LocalDatabase,
+    owners: Option<&OwnerIndex>,
+    resolver: &Resolver,
+    runtimes: &Runtimes,
+    settings: &CommandLineSettings,
+) -> Result<ProcessingPackage, Error<'a>> {
+    let archive_error =
+        |message: &str| Error::Archive(format!("{}: {}", path.to_string_lossy(), message));
+    let mut info = None;
+    let mut files = Vec::new();
+    let mut elf_files = Vec::new();
+    let mut compiled_files = HashMap::new();
+    archive::read_archive_entries(path, |entry, content| {
+        if entry == ".PKGINFO" {
+            info = content.map(|content| archive::parse_pkginfo(&String::from_utf8_lossy(content)));
+            return Ok(());
+        }
+        // the other metadata of pacman is not installed
+        if entry.starts_with('.') {
+            return Ok(());
+        }
+        let file = resolver
+            .locate(&Path::new("/").join(entry))
+            .to_string_lossy()
+            .into_owned();
+        if let Some(content) = content.filter(|content| elf::data_is_elf(content)) {
+            if let Some(elf) = elf::parse_elf(content) {
+                let symbols = if settings.deep || settings.linking {
+                    elf::parse_symbols(content).unwrap_or_default()
+                } else {
+                    Symbols::default()
+                };
+                elf_files.push(ArchiveFile {
+                    file: file.clone(),
+                    elf: Arc::new(elf),
+                    symbols: Arc::new(symbols),
+                });
+            }
+        }
+        if let Some(content) = content.filter(|_| runtime::is_compiled(&file)) {
+            let _ = compiled_files.insert(file.clone(), content.to_vec());
+        }
+        files.push(file);
+        Ok(())
+    })?;
+    let mut info = info.ok_or_else(|| archive_error(".PKGINFO is missing"))?;
+    let depends = info.remove("depend").unwrap_or_default();
+    let mut single_value = |key| info.remove(key).and_then(|mut values| values.pop());
+    let name = single_value("pkgname").ok_or_else(|| archive_error("pkgname is missing"))?;
+    let declared = owners.map(|owners| Declared::new(owners, database, &name, &depends));
+    let mut package = ProcessingPackage {
+        version: single_value("pkgver"),
+        ..ProcessingPackage::new(name)
+    };
 
+    let application = settings.plugins.then(|| {
+        let mut directories = Vec::new();
+        for archive_file in elf_files
+            .iter()
+            .filter(|archive_file| archive_file.elf.executable)
+        {
+            add_search_directories(
+                &mut directories,
+                Path::new(&archive_file.file),
+                &archive_file.elf,
+                resolver,
+            );
+        }
+        directories
+    });
+    package.file_dependencies = elf_files
+        .par_iter()
+        .map(|archive_file| {
+            let path = Path::new(&archive_file.file);
+            let plugin = is_plugin(path, &archive_file.elf, application.as_deref(), resolver);
+            let resolution = resolver.resolve(
+                path,
+                Arc::clone(&archive_file.elf),
+                application
+                    .as_deref()
+                    .filter(|_| plugin)
+                    .unwrap_or_default(),
+            )?;
+            file_dependency(
+                &archive_file.file,
+                &archive_file.elf,
+                plugin,
+                resolution,
+                || Ok(Arc::clone(&archive_file.symbols)),
+                resolver,
+                declared.as_ref(),
+                settings,
+            )
+        })
+        .collect::<Result<Vec<Option<ProcessingFileDependency>>, Error<'_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    // the directories of the archive are not installed yet
+    for file_dependency in &mut package.file_dependencies {
+        file_dependency
+            .search_path_issues
+            .retain(|issue| issue.problem != SearchPathProblem::NotFound);
+    }
+    remove_ignored_or_packaged_libraries(&mut package, &get_filenames_from_files(&files), settings);
+    package.runtime_dependencies =
+        runtimes.verify_files(&files, |file| compiled_files.remove(file));
+    Ok(package)
+}
+
+/// Verifies the elf files and runtime files of `package`
+fn verify_files<'a>(
+    mut package: ProcessingPackage,
+    files: &[String],
+    resolver: &Resolver,
+    runtimes: &Runtimes,
+    scan_cache: Option<&ScanCache>,
+    declared: Option<&Declared<'_>>,
+    settings: &CommandLineSettings,
+) -> Result<ProcessingPackage, Error<'a>> {
+    let filenames = get_filenames_from_files(files);
+    let application = if settings.plugins {
+        Some(application_directories(files, resolver)?)
+    } else {
+        None
+    };
     package.file_dependencies = files
         .par_iter()
         // verify files parallel - will stop if error occures
-        .map(|file| verify_file(file, settings))
+        .map(|file| {
+            verify_file(
+                file,
+                resolver,
+                scan_cache,
+                declared,
+                settings,
+                application.as_deref(),
+            )
+        })
         // collect and abort if error
         .collect::<Result<Vec<Option<ProcessingFileDependency>>, Error<'_>>>()?
         .into_iter()
@@ -52,6 +391,7 @@ fn verify_package<'a>(
         .filter_map(|element| element)
         .collect::<Vec<ProcessingFileDependency>>();
     remove_ignored_or_packaged_libraries(&mut package, &filenames, settings);
+    package.runtime_dependencies = runtimes.verify_files(files, |file| fs::read(file).ok());
 
     Ok(package)
 }
@@ -70,37 +410,357 @@ fn get_filenames_from_files(files: &[String]) -> Vec<String> {
         .collect::<Vec<String>>()
 }
 
+/// Returns the search directories of the programs in `files` which load the
+/// plugins of the package
+fn application_directories<'a>(
+    files: &[String],
+    resolver: &Resolver,
+) -> Result<Vec<PathBuf>, Error<'a>> {
+    let mut directories = Vec::new();
+    for file in files {
+        let path = resolver.locate_file(Path::new(file));
+        if !file_might_be_binary(&path) || !elf::file_is_elf(&path)? {
+            continue;
+        }
+        if let Some(elf) = resolver.load(&path)?.filter(|elf| elf.executable) {
+            add_search_directories(&mut directories, &path, &elf, resolver);
+        }
+    }
+    Ok(directories)
+}
+
+fn add_search_directories(
+    directories: &mut Vec<PathBuf>,
+    path: &Path,
+    elf: &ElfFile,
+    resolver: &Resolver,
+) {
+    for directory in resolver.search_directories(path, elf) {
+        if !directories.contains(&directory) {
+            directories.push(directory);
+        }
+    }
+}
+
+/// Plugins are shared objects outside the linker path. They are only told apart
+/// if the search directories of the programs loading them are given by `application`
+fn is_plugin(
+    path: &Path,
+    elf: &ElfFile,
+    application: Option<&[PathBuf]>,
+    resolver: &Resolver,
+) -> bool {
+    application.is_some() && is_outside_linker_path(path, elf, resolver)
+}
+
+/// Shared objects outside the linker path are loaded by programs, which might
+/// define the symbols they use
+fn is_outside_linker_path(path: &Path, elf: &ElfFile, resolver: &Resolver) -> bool {
+    !elf.executable
+        && !path
+            .parent()
+            .is_some_and(|directory| resolver.is_on_linker_path(directory))
+}
+
+/// Verifies the libraries of `file`. Plugins are only told apart if the search
+/// directories of the programs loading them are given by `application`
 fn verify_file<'a>(
     file: &str,
+    resolver: &Resolver,
+    scan_cache: Option<&ScanCache>,
+    declared: Option<&Declared<'_>>,
     settings: &CommandLineSettings,
+    application: Option<&[PathBuf]>,
 ) -> Result<Option<ProcessingFileDependency>, Error<'a>> {
-    if file_might_be_binary(file) && cmd::file_is_elf(file)? {
-        let dependency = match settings.command {
-            Command::Ldd => cmd::verify_files_via_ldd(file),
-            Command::Readelf => cmd::verify_files_via_readelf(file),
-        }?;
-        return Ok(dependency);
+    // the file is read where its symlinks lead inside of the checked system
+    let path = &resolver.locate_file(Path::new(file));
+    if !file_might_be_binary(path) {
+        return Ok(None);
     }
-    Ok(None)
+    let cache = scan_cache.and_then(|scan_cache| Some((scan_cache, FileKey::read(path)?)));
+    let (elf, resolution) = match cache.and_then(|(scan_cache, key)| scan_cache.get(path, key)) {
+        Some(CachedFile::NotElf) => return Ok(None),
+        Some(CachedFile::Elf(elf, resolution)) => (elf, resolution),
+        None => {
+            let elf = if elf::file_is_elf(path)? {
+                resolver.load(path)?
+            } else {
+                None
+            };
+            let Some(elf) = elf else {
+                if let Some((scan_cache, key)) = cache {
+                    scan_cache.insert(path, key, None, None);
+                }
+                return Ok(None);
+            };
+            (elf, None)
+        }
+    };
+    let plugin = is_plugin(path, &elf, application, resolver);
+    let resolution = match resolution {
+        Some(resolution) if !plugin => resolution,
+        _ => {
+            let application = application.filter(|_| plugin).unwrap_or_default();
+            let resolution = resolver.resolve(path, Arc::clone(&elf), application)?;
+            if let Some((scan_cache, key)) = cache {
+                // the resolution of plugins depends on the programs of the package
+                let result = Some(&resolution).filter(|_| !plugin);
+                scan_cache.insert(path, key, Some(Arc::clone(&elf)), result);
+            }
+            resolution
+        }
+    };
+    file_dependency(
+        file,
+        &elf,
+        plugin,
+        resolution,
+        || resolver.load_symbols(path),
+        resolver,
+        declared,
+        settings,
+    )
 }
 
-fn file_might_be_binary(file: &str) -> bool {
-    let path = PathBuf::from(file);
-    if !path.is_file() {
-        return false;
+/// Collects the findings of `file` from its resolution. `symbols` returns the
+/// dynamic symbols of the file, which are only needed for the deep verification
+/// and the linking analysis
+#[allow(clippy::too_many_arguments)]
+fn file_dependency<'a, F>(
+    file: &str,
+    elf: &ElfFile,
+    plugin: bool,
+    resolution: Resolution,
+    symbols: F,
+    resolver: &Resolver,
+    declared: Option<&Declared<'_>>,
+    settings: &CommandLineSettings,
+) -> Result<Option<ProcessingFileDependency>, Error<'a>>
+where
+    F: FnOnce() -> Result<Arc<Symbols>, Error<'a>>,
+{
+    // without all libraries every symbol of the missing ones would be reported as well
+    let symbols = if (settings.deep || settings.linking) && resolution.missing.is_empty() {
+        Some(symbols()?)
+    } else {
+        None
+    };
+    let symbol_dependencies = match symbols {
+        Some(ref symbols)
+            if settings.deep
+                && !is_outside_linker_path(
+                    &resolver.locate_file(Path::new(file)),
+                    elf,
+                    resolver,
+                ) =>
+        {
+            find_undefined_symbols(symbols, &resolution, resolver)?
+        }
+        _ => HashSet::new(),
+    };
+    let (unused_libraries, underlinked_symbols) = match symbols {
+        Some(ref symbols) if settings.linking => {
+            analyze_linking(symbols, elf, &resolution, resolver)?
+        }
+        _ => (HashSet::new(), HashSet::new()),
+    };
+    let undeclared_dependencies = declared.map_or_else(HashSet::new, |declared| {
+        undeclared_dependencies(elf, &resolution, declared)
+    });
+    let fragile_dependencies = if settings.fragile {
+        resolution
+            .searched
+            .iter()
+            .filter_map(fragile_library)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    let search_path_issues =
+        search_path_issues(&resolver.locate_file(Path::new(file)), elf, resolver);
+    if resolution.missing.is_empty()
+        && resolution.missing_versions.is_empty()
+        && symbol_dependencies.is_empty()
+        && fragile_dependencies.is_empty()
+        && search_path_issues.is_empty()
+        && unused_libraries.is_empty()
+        && underlinked_symbols.is_empty()
+        && undeclared_dependencies.is_empty()
+    {
+        return Ok(None);
     }
-    if let Some(extension) = path.extension() {
-        if let Some(ext) = extension.to_str() {
-            return match ext {
-                "a" | "png" | "la" | "ttf" | "gz" | "html" | "css" | "h" | "c" | "cxx" | "xml"
-                | "rgb" | "gif" | "wav" | "ogg" | "ogv" | "avi" | "opus" | "mp3" | "po" | "txt"
-                | "jpg" | "jpeg" | "bmp" | "xcf" | "mo" | "rb" | "py" | "lua" | "config"
-                | "cfg" | "svg" | "desktop" | "conf" | "pdf" | "xz" => false,
-                _ => true,
+    Ok(Some(ProcessingFileDependency {
+        file_name: String::from(file),
+        architecture: elf.architecture,
+        plugin,
+        library_dependencies: resolution.missing.into_iter().collect(),
+        version_dependencies: resolution.missing_versions.into_iter().collect(),
+        symbol_dependencies,
+        architecture_mismatches: resolution.mismatched.into_iter().collect(),
+        fragile_dependencies,
+        search_path_issues,
+        unused_libraries,
+        underlinked_symbols,
+        undeclared_dependencies,
+    }))
+}
+
+/// Returns the direct libraries of a file which are owned by a package that is
+/// not declared as a dependency
+fn undeclared_dependencies(
+    elf: &ElfFile,
+    resolution: &Resolution,
+    declared: &Declared<'_>,
+) -> HashSet<UndeclaredDependency> {
+    resolution
+        .direct_libraries(elf)
+        .into_iter()
+        .filter_map(|(library, path)| {
+            let owner = declared.owners.owner(path)?;
+            (!declared.packages.contains(owner)).then(|| UndeclaredDependency {
+                library: String::from(library),
+                path: path.to_string_lossy().into_owned(),
+                package: String::from(owner),
+            })
+        })
+        .collect()
+}
+
+/// Checks the `DT_RPATH` and `DT_RUNPATH` entries of the elf file at `path`
+/// for directories leaked from the build environment or missing on the system
+fn search_path_issues(path: &Path, elf: &ElfFile, resolver: &Resolver) -> Vec<SearchPathIssue> {
+    let entries = elf
+        .rpath
+        .iter()
+        .map(|entry| ("RPATH", entry))
+        .chain(elf.runpath.iter().map(|entry| ("RUNPATH", entry)));
+    entries
+        .zip(resolver.search_directories(path, elf))
+        .filter_map(|((tag, entry), directory)| {
+            let origin = entry.contains("$ORIGIN") || entry.contains("${ORIGIN}");
+            let problem = if !origin && !entry.starts_with('/') {
+                SearchPathProblem::Relative
+            } else if !origin && entry != "/usr" && !entry.starts_with("/usr/") {
+                SearchPathProblem::OutsideUsr
+            } else if !directory.is_dir() {
+                SearchPathProblem::NotFound
+            } else {
+                return None;
             };
+            Some(SearchPathIssue {
+                tag,
+                entry: entry.clone(),
+                problem,
+            })
+        })
+        .collect()
+}
+
+/// Checks whether a library found outside of the linker path is only found
+/// on the current system. Libraries found relative to `$ORIGIN` inside of
+/// the package are removed with the packaged libraries
+fn fragile_library(searched: &SearchedLibrary) -> Option<FragileLibrary> {
+    let reason = match searched.source {
+        SearchSource::Environment => Fragility::Environment,
+        SearchSource::SearchPath { ref directory, .. }
+            if BUILD_DIRECTORIES
+                .iter()
+                .any(|build| format!("{}/", directory).starts_with(build)) =>
+        {
+            Fragility::BuildDirectory(directory.clone())
         }
-    }
-    true
+        SearchSource::SearchPath {
+            ref directory,
+            origin: true,
+        } => Fragility::OriginOutsidePackage(directory.clone()),
+        SearchSource::SearchPath { .. } => return None,
+    };
+    Some(FragileLibrary {
+        library: searched.library.clone(),
+        path: searched.path.to_string_lossy().into_owned(),
+        reason,
+    })
+}
+
+/// Returns the undefined `symbols` of a file which none of the loaded libraries
+/// define, like `ldd -r` does
+fn find_undefined_symbols<'a>(
+    symbols: &Symbols,
+    resolution: &Resolution,
+    resolver: &Resolver,
+) -> Result<HashSet<String>, Error<'a>> {
+    let libraries = resolution
+        .libraries
+        .iter()
+        .map(|library| resolver.load_symbols(library))
+        .collect::<Result<Vec<_>, Error<'_>>>()?;
+    Ok(symbols
+        .undefined
+        .iter()
+        .filter(|symbol| {
+            !libraries
+                .iter()
+                .any(|library| library.defined.contains(*symbol))
+        })
+        .cloned()
+        .collect())
+}
+
+/// Returns the direct libraries of a file which define none of its undefined
+/// `symbols` and the symbols it uses which only indirectly loaded libraries define
+fn analyze_linking<'a>(
+    symbols: &Symbols,
+    elf: &ElfFile,
+    resolution: &Resolution,
+    resolver: &Resolver,
+) -> Result<(HashSet<String>, HashSet<UnderlinkedSymbol>), Error<'a>> {
+    let direct = resolution.direct_libraries(elf);
+    let direct_symbols = direct
+        .iter()
+        .map(|(_, path)| resolver.load_symbols(path))
+        .collect::<Result<Vec<_>, Error<'_>>>()?;
+    let unused = direct
+        .iter()
+        .zip(&direct_symbols)
+        .filter(|(_, library)| {
+            !symbols
+                .undefined
+                .iter()
+                .any(|symbol| library.defined.contains(symbol))
+        })
+        .map(|((name, _), _)| String::from(*name))
+        .collect();
+    let indirect = resolution
+        .libraries
+        .get(direct.len()..)
+        .unwrap_or_default()
+        .iter()
+        .map(|path| Ok((path, resolver.load_symbols(path)?)))
+        .collect::<Result<Vec<_>, Error<'_>>>()?;
+    let underlinked = symbols
+        .undefined
+        .iter()
+        .filter(|symbol| {
+            !direct_symbols
+                .iter()
+                .any(|library| library.defined.contains(*symbol))
+        })
+        .filter_map(|symbol| {
+            let (path, _) = indirect
+                .iter()
+                .find(|(_, library)| library.defined.contains(symbol))?;
+            Some(UnderlinkedSymbol {
+                symbol: symbol.clone(),
+                library: path.to_string_lossy().into_owned(),
+            })
+        })
+        .collect();
+    Ok((unused, underlinked))
+}
+
+fn file_might_be_binary(path: &Path) -> bool {
+    // directories and broken symlinks are part of the file list as well
+    path.is_file()
 }
 
 fn remove_ignored_or_packaged_libraries(
@@ -108,30 +768,56 @@ fn remove_ignored_or_packaged_libraries(
     filenames: &[String],
     settings: &CommandLineSettings,
 ) {
+    let is_ignored_or_packaged = |library_dependency: &String| {
+        settings.ignore_libraries.contains(library_dependency)
+            || filenames.contains(library_dependency)
+            || settings
+                .ignore_libraries_regex
+                .iter()
+                .fold(false, |_, ignore_libraries_regex| {
+                    ignore_libraries_regex.is_match(library_dependency)
+                })
+    };
     package
         .file_dependencies
         .iter_mut()
         .for_each(|file_dependency| {
             file_dependency
                 .library_dependencies
-                .retain(|library_dependency| {
-                    !settings.ignore_libraries.contains(library_dependency)
-                        && !filenames.contains(library_dependency)
-                        && !settings.ignore_libraries_regex.iter().fold(
-                            false,
-                            |_, ignore_libraries_regex| {
-                                ignore_libraries_regex.is_match(library_dependency)
-                            },
-                        )
-                });
+                .retain(|library_dependency| !is_ignored_or_packaged(library_dependency));
+            // the versions of ignored libraries are ignored as well
+            file_dependency
+                .version_dependencies
+                .retain(|version_dependency| !is_ignored_or_packaged(&version_dependency.library));
+            file_dependency
+                .fragile_dependencies
+                .retain(|fragile| !is_ignored_or_packaged(&fragile.library));
+            file_dependency
+                .undeclared_dependencies
+                .retain(|undeclared| !is_ignored_or_packaged(&undeclared.library));
+            let library_dependencies = &file_dependency.library_dependencies;
+            file_dependency
+                .architecture_mismatches
+                .retain(|mismatch| library_dependencies.contains(&mismatch.library));
         });
-    package
-        .file_dependencies
-        .retain(|file_dependency| !file_dependency.library_dependencies.is_empty());
+    package.file_dependencies.retain(|file_dependency| {
+        !file_dependency.library_dependencies.is_empty()
+            || !file_dependency.version_dependencies.is_empty()
+            || !file_dependency.symbol_dependencies.is_empty()
+            || !file_dependency.fragile_dependencies.is_empty()
+            || !file_dependency.search_path_issues.is_empty()
+            || !file_dependency.unused_libraries.is_empty()
+            || !file_dependency.underlinked_symbols.is_empty()
+            || !file_dependency.undeclared_dependencies.is_empty()
+    });
 }
 
-fn setup_library_requirements<'a>(package: &mut Package) -> Result<(), Error<'a>> {
+fn setup_library_requirements<'a>(
+    package: &mut Package,
+    ghc_versions: &[String],
+) -> Result<(), Error<'a>> {
     let mut cache: HashMap<String, LibraryRequired> = HashMap::new();
+    let mut haskell: BTreeMap<String, HaskellRequired> = BTreeMap::new();
     package
         .file_dependencies
         .iter()
@@ -140,7 +826,28 @@ fn setup_library_requirements<'a>(package: &mut Package) -> Result<(), Error<'a>
                 .library_dependencies
                 .iter()
                 .for_each(|library_dependency| {
-                    if cache.contains_key(&**library_dependency) {
+                    // hashed haskell sonames are grouped by the ghc version which built them
+                    if let Some(library) = HaskellLibrary::parse(library_dependency) {
+                        let requirement = haskell
+                            .entry(library.ghc_version.clone())
+                            .or_insert_with(|| HaskellRequired {
+                                ghc_version: library.ghc_version.clone(),
+                                installed_versions: ghc_versions.to_vec(),
+                                libraries: vec![],
+                                files_requiring: vec![],
+                            });
+                        if !requirement.libraries.contains(&library.package) {
+                            requirement.libraries.push(library.package);
+                        }
+                        if !requirement
+                            .files_requiring
+                            .contains(&file_dependency.file_name)
+                        {
+                            requirement
+                                .files_requiring
+                                .push(Rc::<String>::clone(&file_dependency.file_name));
+                        }
+                    } else if cache.contains_key(&**library_dependency) {
                         if let Some(value) = cache.get_mut(&**library_dependency) {
                             value
                                 .files_requiring
@@ -160,19 +867,101 @@ fn setup_library_requirements<'a>(package: &mut Package) -> Result<(), Error<'a>
                 })
         });
     package.library_requirements = cache.into_iter().map(|(_, val)| val).collect();
+    package.haskell_requirements = haskell
+        .into_values()
+        .map(|mut requirement| {
+            requirement.libraries.sort();
+            requirement
+        })
+        .collect();
     Ok(())
 }
 
-fn setup_packages_containing<'a>(package: &mut Package) -> Result<(), Error<'a>> {
+fn setup_version_requirements(package: &mut Package) {
+    let mut requirements: HashMap<MissingVersion, VersionRequired> = HashMap::new();
+    for file_dependency in &package.file_dependencies {
+        for version_dependency in &file_dependency.version_dependencies {
+            requirements
+                .entry(version_dependency.clone())
+                .or_insert_with(|| VersionRequired {
+                    version: version_dependency.clone(),
+                    files_requiring: vec![],
+                })
+                .files_requiring
+                .push(Rc::<String>::clone(&file_dependency.file_name));
+        }
+    }
+    package.version_requirements = requirements.into_values().collect();
+}
+
+fn setup_symbol_requirements(package: &mut Package) {
+    let mut requirements: HashMap<Rc<String>, SymbolRequired> = HashMap::new();
+    for file_dependency in &package.file_dependencies {
+        for symbol in &file_dependency.symbol_dependencies {
+            requirements
+                .entry(Rc::clone(symbol))
+                .or_insert_with(|| SymbolRequired {
+                    symbol_name: Rc::clone(symbol),
+                    files_requiring: vec![],
+                })
+                .files_requiring
+                .push(Rc::<String>::clone(&file_dependency.file_name));
+        }
+    }
+    package.symbol_requirements = requirements.into_values().collect();
+}
+
+fn setup_library_replacements(package: &mut Package, library_index: &LibraryIndex) {
+    package.library_replacements = package
+        .library_requirements
+        .iter()
+        .map(|library| LibraryReplacement {
+            library_name: Rc::<String>::clone(&library.library_name),
+            replaced_by: library_index.find(&library.library_name),
+        })
+        .filter(|replacement| !replacement.replaced_by.is_empty())
+        .collect::<Vec<LibraryReplacement>>();
+}
+
+fn setup_packages_containing(package: &mut Package, file_index: &FileIndex, resolver: &Resolver) {
     package.packages_containing = package
         .library_requirements
         .iter()
         .map(|library| {
-            Ok(PackagesContaining {
+            let architectures = package
+                .file_dependencies
+                .iter()
+                .filter(|dependency| {
+                    dependency
+                        .library_dependencies
+                        .contains(&library.library_name)
+                })
+                .map(|dependency| dependency.architecture)
+                .collect::<HashSet<Architecture>>();
+            let mut candidates = file_index
+                .find(&library.library_name)
+                .into_iter()
+                .map(|file| {
+                    let directory = resolver.locate(
+                        Path::new(&file.path)
+                            .parent()
+                            .unwrap_or_else(|| Path::new("/")),
+                    );
+                    PackageCandidate {
+                        on_linker_path: resolver.is_on_linker_path(&directory),
+                        architecture_matches: architectures.iter().all(|architecture| {
+                            resolver.directory_matches_architecture(&directory, *architecture)
+                        }),
+                        file,
+                    }
+                })
+                .collect::<Vec<PackageCandidate>>();
+            // candidates satisfying the requirement are listed first
+            candidates.sort_by_key(|candidate| !candidate.is_usable());
+            PackagesContaining {
                 library_name: Rc::<String>::clone(&library.library_name),
-                packages_containing: cmd::get_packages_containing_library(&library.library_name)?,
-            })
+                packages_containing: candidates,
+            }
         })
-        .collect::<Result<Vec<PackagesContaining>, Error<'_>>>()?;
-    Ok(())
+        .collect::<Vec<PackagesContaining>>();
 }
@@ -0,0 +1,550 @@
+use crate::{
+    data::{Error, MismatchedLibrary, MissingVersion},
+    elf::{self, Architecture, Class, ElfFile, Symbols},
+    ldcache::{self, LdCache},
+    root,
+};
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+const LD_SO_CONF: &str = "/etc/ld.so.conf";
+const LD_SO_CACHE: &str = "/etc/ld.so.cache";
+/// `lib32` only contains libraries of 32-bit files and `lib64` of 64-bit files
+const TRUSTED_DIRECTORIES: [&str; 6] = [
+    "/lib64",
+    "/usr/lib64",
+    "/lib32",
+    "/usr/lib32",
+    "/lib",
+    "/usr/lib",
+];
+
+/// Emulates the library search of the dynamic linker `ld.so` without
+/// executing any code of the checked files.
+#[derive(Debug)]
+pub struct Resolver {
+    /// the checked system is installed below this directory
+    root: PathBuf,
+    /// `LD_LIBRARY_PATH` of the current environment
+    library_path: Vec<PathBuf>,
+    /// `/etc/ld.so.cache` if it is up to date
+    cache: Option<LdCache>,
+    /// directories of `/etc/ld.so.conf`, searched instead of a stale cache
+    configured_directories: Vec<PathBuf>,
+    /// files included by `/etc/ld.so.conf`
+    configuration_files: Vec<PathBuf>,
+    trusted_directories: Vec<PathBuf>,
+    /// every elf file is only parsed once. `None` marks files which are not elf files
+    elf_files: Mutex<HashMap<PathBuf, Option<Arc<ElfFile>>>>,
+    /// the dynamic symbols are only read for the deep verification
+    symbols: Mutex<HashMap<PathBuf, Arc<Symbols>>>,
+}
+
+/// The result of loading an elf file and its libraries
+#[derive(Debug, Default, Clone)]
+pub struct Resolution {
+    pub missing: Vec<String>,
+    /// symbol versions not defined by the loaded libraries
+    pub missing_versions: Vec<MissingVersion>,
+    /// paths of the loaded libraries in the order they were loaded
+    pub libraries: Vec<PathBuf>,
+    /// missing libraries which only exist for another architecture
+    pub mismatched: Vec<MismatchedLibrary>,
+    /// libraries only found through `LD_LIBRARY_PATH` or the search paths of
+    /// the objects, which are not available on the linker path
+    pub searched: Vec<SearchedLibrary>,
+    /// `DT_RPATH` and `DT_RUNPATH` directories the libraries were searched in
+    pub directories: Vec<PathBuf>,
+}
+
+impl Resolution {
+    /// Returns the `DT_NEEDED` entries of `elf` which were found together with
+    /// their paths. The libraries of an object are loaded before the libraries
+    /// of its libraries, so they are the first ones loaded
+    pub fn direct_libraries<'b>(&'b self, elf: &'b ElfFile) -> Vec<(&'b str, &'b Path)> {
+        let mut names: Vec<&str> = Vec::new();
+        for library in &elf.needed {
+            if !names.contains(&library.as_str()) && !self.missing.contains(library) {
+                names.push(library);
+            }
+        }
+        names
+            .into_iter()
+            .zip(self.libraries.iter().map(PathBuf::as_path))
+            .collect()
+    }
+}
+
+/// A library found outside of the linker path
+#[derive(Debug, Clone)]
+pub struct SearchedLibrary {
+    pub library: String,
+    pub path: PathBuf,
+    pub source: SearchSource,
+}
+
+/// The directory outside of the linker path a library was found in
+#[derive(Debug, Clone)]
+pub enum SearchSource {
+    /// a directory of `LD_LIBRARY_PATH`
+    Environment,
+    /// a `DT_RPATH` or `DT_RUNPATH` directory of the checked system. `origin`
+    /// is set if the directory is relative to `$ORIGIN`
+    SearchPath { directory: String, origin: bool },
+}
+
+/// The outcome of searching a library
+enum Found {
+    /// the library and the directory outside of the linker path it was found in
+    Library(PathBuf, Arc<ElfFile>, Option<SearchSource>),
+    /// only a library of another architecture exists, like the 64-bit
+    /// library while a 32-bit file requires it
+    OtherArchitecture(PathBuf, Architecture),
+    Missing,
+}
+
+/// An object loaded by the emulated dynamic linker
+struct LoadedObject {
+    elf: Arc<ElfFile>,
+    origin: PathBuf,
+    /// index of the object which required this object
+    loader: Option<usize>,
+}
+
+impl Resolver {
+    /// Creates a resolver for the system installed below `root`. The
+    /// `LD_LIBRARY_PATH` of the current environment is only searched with `use_environment`
+    pub fn new(root: &Path, use_environment: bool) -> Self {
+        // the environment of the running system does not apply to another root
+        let library_path = env::var_os("LD_LIBRARY_PATH")
+            .filter(|_| use_environment && root::is_host(root))
+            .map(|library_path| {
+                env::split_paths(&library_path)
+                    .filter(|path| !path.as_os_str().is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let configuration =
+            ldcache::read_ld_so_conf(&root::join(root, Path::new(LD_SO_CONF)), root);
+        let trusted_directories = TRUSTED_DIRECTORIES
+            .iter()
+            .map(|directory| root::join(root, Path::new(directory)))
+            .collect::<Vec<PathBuf>>();
+        let cache_path = root::join(root, Path::new(LD_SO_CACHE));
+        let cache = if ldcache::is_stale(&cache_path, &configuration, &trusted_directories) {
+            None
+        } else {
+            ldcache::read_ld_so_cache(&cache_path)
+        };
+        Self {
+            root: root.to_path_buf(),
+            library_path,
+            cache,
+            configured_directories: configuration.directories,
+            configuration_files: configuration.files,
+            trusted_directories,
+            elf_files: Mutex::new(HashMap::new()),
+            symbols: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the files and directories whose content decides where libraries
+    /// are found, apart from the search paths of the elf files themselves
+    pub fn state_paths(&self) -> Vec<PathBuf> {
+        // the configuration files include `/etc/ld.so.conf` itself
+        let mut paths = vec![root::join(&self.root, Path::new(LD_SO_CACHE))];
+        paths.extend(self.configuration_files.iter().cloned());
+        paths.extend(self.library_path.iter().cloned());
+        paths.extend(self.configured_directories.iter().cloned());
+        paths.extend(self.trusted_directories.iter().cloned());
+        paths
+    }
+
+    /// Reads the elf file at `path` or returns the already parsed one
+    pub fn load<'a>(&self, path: &Path) -> Result<Option<Arc<ElfFile>>, Error<'a>> {
+        if let Some(elf) = self
+            .elf_files
+            .lock()
+            .expect("Lock should not be poisoned")
+            .get(path)
+        {
+            return Ok(elf.clone());
+        }
+        let elf = elf::read_elf(path)?.map(Arc::new);
+        let _ = self
+            .elf_files
+            .lock()
+            .expect("Lock should not be poisoned")
+            .insert(path.to_path_buf(), elf.clone());
+        Ok(elf)
+    }
+
+    /// Reads the dynamic symbols of the elf file at `path` or returns the already
+    /// read ones. Files which cannot be parsed have no symbols
+    pub fn load_symbols<'a>(&self, path: &Path) -> Result<Arc<Symbols>, Error<'a>> {
+        if let Some(symbols) = self
+            .symbols
+            .lock()
+            .expect("Lock should not be poisoned")
+            .get(path)
+        {
+            return Ok(Arc::clone(symbols));
+        }
+        let symbols = Arc::new(elf::read_symbols(path)?.unwrap_or_default());
+        let _ = self
+            .symbols
+            .lock()
+            .expect("Lock should not be poisoned")
+            .insert(path.to_path_buf(), Arc::clone(&symbols));
+        Ok(symbols)
+    }
+
+    /// Loads the libraries of `elf` and their libraries in the same order as the
+    /// dynamic linker. `application` contains the search directories of the program loading `file` with `dlopen`.
+    #[allow(clippy::indexing_slicing)]
+    pub fn resolve<'a>(
+        &self,
+        file: &Path,
+        elf: Arc<ElfFile>,
+        application: &[PathBuf],
+    ) -> Result<Resolution, Error<'a>> {
+        let mut resolution = Resolution::default();
+        let mut objects = vec![LoadedObject {
+            elf,
+            origin: get_origin(file),
+            loader: None,
+        }];
+        let mut loaded = HashMap::new();
+        let mut index = 0;
+        while index < objects.len() {
+            let elf = Arc::clone(&objects[index].elf);
+            let origin = &objects[index].origin;
+            resolution.directories.extend(
+                expand_origin(&elf.rpath, origin, &self.root).chain(expand_origin(
+                    &elf.runpath,
+                    origin,
+                    &self.root,
+                )),
+            );
+            for library in &elf.needed {
+                if loaded.contains_key(library) {
+                    // libraries are only loaded once per process
+                    continue;
+                }
+                let found = match self.find_library(library, &objects, index, application)? {
+                    Found::Library(path, library_elf, source) => {
+                        if let Some(source) = source {
+                            let architecture = library_elf.architecture;
+                            if !self.is_on_system(library, architecture)? {
+                                resolution.searched.push(SearchedLibrary {
+                                    library: library.clone(),
+                                    path: path.clone(),
+                                    source,
+                                });
+                            }
+                        }
+                        resolution.libraries.push(path.clone());
+                        objects.push(LoadedObject {
+                            elf: Arc::clone(&library_elf),
+                            origin: get_origin(&path),
+                            loader: Some(index),
+                        });
+                        Some(library_elf)
+                    }
+                    Found::OtherArchitecture(path, architecture) => {
+                        resolution.missing.push(library.clone());
+                        resolution.mismatched.push(MismatchedLibrary {
+                            library: library.clone(),
+                            path: path.to_string_lossy().into_owned(),
+                            architecture,
+                        });
+                        None
+                    }
+                    Found::Missing => {
+                        resolution.missing.push(library.clone());
+                        None
+                    }
+                };
+                let _ = loaded.insert(library.clone(), found);
+            }
+            // versions of missing libraries are already reported by the missing library
+            for requirement in &elf.version_requirements {
+                let Some(Some(library_elf)) = loaded.get(&requirement.library) else {
+                    continue;
+                };
+                resolution.missing_versions.extend(
+                    requirement
+                        .versions
+                        .iter()
+                        .filter(|version| !library_elf.version_definitions.contains(version))
+                        .map(|version| MissingVersion {
+                            library: requirement.library.clone(),
+                            version: version.clone(),
+                        }),
+                );
+            }
+            index += 1;
+        }
+        Ok(resolution)
+    }
+
+    /// Searches a library required by `objects[index]` in the order of `ld.so`:
+    /// `DT_RPATH` of the requiring object and its loaders (only if the requiring
+    /// object has no `DT_RUNPATH`), `LD_LIBRARY_PATH`, `DT_RUNPATH`, `/etc/ld.so.cache`
+    /// and finally the trusted system directories. The directories of the loading
+    /// `application` are searched after those of the objects
+    #[allow(clippy::indexing_slicing)]
+    fn find_library<'a>(
+        &self,
+        library: &str,
+        objects: &[LoadedObject],
+        index: usize,
+        application: &[PathBuf],
+    ) -> Result<Found, Error<'a>> {
+        let requiring = &objects[index];
+        let architecture = requiring.elf.architecture;
+        // names containing a slash are loaded directly without searching
+        if library.contains('/') {
+            let path = root::join(&self.root, Path::new(library));
+            return self.search(vec![(path, None)], Vec::new(), architecture);
+        }
+
+        let mut directories = Vec::new();
+        if requiring.elf.runpath.is_empty() {
+            let mut loader = Some(index);
+            while let Some(current) = loader {
+                let object = &objects[current];
+                directories.extend(self.search_path(&object.elf.rpath, &object.origin));
+                loader = object.loader;
+            }
+        }
+        directories.extend(
+            self.library_path
+                .iter()
+                .map(|directory| (directory.clone(), Some(SearchSource::Environment))),
+        );
+        directories.extend(self.search_path(&requiring.elf.runpath, &requiring.origin));
+        directories.extend(
+            application
+                .iter()
+                .map(|directory| (directory.clone(), None)),
+        );
+
+        let paths = directories
+            .into_iter()
+            .map(|(directory, source)| (directory.join(library), source))
+            .chain(
+                self.system_paths(library, architecture)
+                    .into_iter()
+                    .map(|path| (path, None)),
+            )
+            .collect();
+        // libraries of other architectures are only searched to report them
+        let other = self
+            .cache
+            .iter()
+            .flat_map(|cache| cache.lookup_any(library))
+            .map(|path| root::join(&self.root, path))
+            .chain(
+                self.trusted_directories
+                    .iter()
+                    .map(|directory| directory.join(library)),
+            )
+            .collect();
+        self.search(paths, other, architecture)
+    }
+
+    /// Returns the paths `library` is searched at on the linker path:
+    /// `/etc/ld.so.cache` and the trusted system directories
+    fn system_paths(&self, library: &str, architecture: Architecture) -> Vec<PathBuf> {
+        // without an up to date cache the configured directories are searched instead
+        let cached = self.cache.as_ref().map_or_else(
+            || {
+                self.configured_directories
+                    .iter()
+                    .map(|directory| directory.join(library))
+                    .collect::<Vec<PathBuf>>()
+            },
+            |cache| {
+                cache
+                    .lookup(library, architecture)
+                    .map(|path| root::join(&self.root, path))
+                    .collect()
+            },
+        );
+        let trusted = self
+            .trusted_directories
+            .iter()
+            .filter(|directory| directory_matches_class(directory, architecture.class))
+            .map(|directory| directory.join(library));
+        cached.into_iter().chain(trusted).collect()
+    }
+
+    /// Checks whether `library` is found on the linker path as well
+    fn is_on_system<'a>(
+        &self,
+        library: &str,
+        architecture: Architecture,
+    ) -> Result<bool, Error<'a>> {
+        let paths = self
+            .system_paths(library, architecture)
+            .into_iter()
+            .map(|path| (path, None))
+            .collect();
+        Ok(matches!(
+            self.search(paths, Vec::new(), architecture)?,
+            Found::Library(..)
+        ))
+    }
+
+    /// Expands the `DT_RPATH` or `DT_RUNPATH` `directories` of an object
+    /// together with the source reported for the libraries found in them
+    fn search_path<'b>(
+        &'b self,
+        directories: &'b [String],
+        origin: &'b Path,
+    ) -> impl Iterator<Item = (PathBuf, Option<SearchSource>)> + 'b {
+        directories
+            .iter()
+            .zip(expand_origin(directories, origin, &self.root))
+            .map(move |(directory, expanded)| {
+                let source = SearchSource::SearchPath {
+                    directory: String::from(root::strip(&self.root, &expanded.to_string_lossy())),
+                    origin: directory.contains("$ORIGIN") || directory.contains("${ORIGIN}"),
+                };
+                (expanded, Some(source))
+            })
+    }
+
+    /// Checks whether the dynamic linker searches `directory` without an
+    /// `DT_RPATH`, `DT_RUNPATH` or `LD_LIBRARY_PATH` pointing to it
+    pub fn is_on_linker_path(&self, directory: &Path) -> bool {
+        let directory = self.locate_file(directory);
+        self.configured_directories
+            .iter()
+            .chain(&self.trusted_directories)
+            .any(|linker_directory| self.locate_file(linker_directory) == directory)
+    }
+
+    /// Returns the `DT_RPATH` and `DT_RUNPATH` directories of the elf file at `file`
+    pub fn search_directories(&self, file: &Path, elf: &ElfFile) -> Vec<PathBuf> {
+        let origin = get_origin(file);
+        expand_origin(&elf.rpath, &origin, &self.root)
+            .chain(expand_origin(&elf.runpath, &origin, &self.root))
+            .collect()
+    }
+
+    /// Returns the location of the absolute `path` of the checked system
+    pub fn locate(&self, path: &Path) -> PathBuf {
+        root::join(&self.root, path)
+    }
+
+    /// Follows the symlinks of `file`, which is already located below the root,
+    /// like `/lib` pointing to `/usr/lib`
+    pub fn locate_file(&self, file: &Path) -> PathBuf {
+        root::follow(&self.root, file)
+    }
+
+    /// Checks whether libraries in `directory` are usable by `architecture`. If
+    /// `/etc/ld.so.cache` does not know the directory, its name is used instead
+    /// (`lib32`, `libx32`, `lib64`)
+    pub fn directory_matches_architecture(
+        &self,
+        directory: &Path,
+        architecture: Architecture,
+    ) -> bool {
+        let directory = self.locate_file(directory);
+        // the cache contains the paths of the checked system
+        let cached_directory =
+            Path::new("/").join(directory.strip_prefix(&self.root).unwrap_or(&directory));
+        if let Some(matches) = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.directory_matches_architecture(&cached_directory, architecture))
+        {
+            return matches;
+        }
+        let name = directory
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let class = if name.ends_with("32") {
+            Class::Elf32
+        } else if name.ends_with("64") || cfg!(target_pointer_width = "64") {
+            Class::Elf64
+        } else {
+            Class::Elf32
+        };
+        class == architecture.class
+    }
+
+    /// Loads the first of `paths` which is an elf file usable by `architecture`.
+    /// The dynamic linker silently skips incompatible files, the first one of
+    /// `paths` and `other` is returned if no usable file exists
+    fn search<'a>(
+        &self,
+        paths: Vec<(PathBuf, Option<SearchSource>)>,
+        other: Vec<PathBuf>,
+        architecture: Architecture,
+    ) -> Result<Found, Error<'a>> {
+        let mut found = Found::Missing;
+        let candidates = paths
+            .into_iter()
+            .map(|(path, source)| (path, Some(source)))
+            .chain(other.into_iter().map(|path| (path, None)));
+        for (path, usable) in candidates {
+            if !path.is_file() {
+                continue;
+            }
+            let Some(elf) = self.load(&path)? else {
+                continue;
+            };
+            if elf.architecture == architecture {
+                if let Some(source) = usable {
+                    return Ok(Found::Library(path, elf, source));
+                }
+            } else if matches!(found, Found::Missing) {
+                found = Found::OtherArchitecture(path, elf.architecture);
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Checks whether `directory` may contain libraries of `class`, judged by its name
+fn directory_matches_class(directory: &Path, class: Class) -> bool {
+    let name = directory.to_string_lossy();
+    match class {
+        Class::Elf32 => !name.ends_with("64"),
+        Class::Elf64 => !name.ends_with("32"),
+    }
+}
+
+fn get_origin(file: &Path) -> PathBuf {
+    file.parent()
+        .map_or_else(|| PathBuf::from("/"), Path::to_path_buf)
+}
+
+/// Replaces `$ORIGIN` by the directory of the file. Other directories are
+/// located below `root`, the origin already is
+fn expand_origin<'b>(
+    directories: &'b [String],
+    origin: &'b Path,
+    root: &'b Path,
+) -> impl Iterator<Item = PathBuf> + 'b {
+    let origin = origin.to_string_lossy();
+    directories.iter().map(move |directory| {
+        let expanded = directory
+            .replace("${ORIGIN}", &origin)
+            .replace("$ORIGIN", &origin);
+        if expanded == *directory {
+            root::join(root, Path::new(directory))
+        } else {
+            PathBuf::from(expanded)
+        }
+    })
+}
@@ -0,0 +1,125 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// The number of symlinks followed before a path is considered a loop, like `ELOOP`
+const MAX_SYMLINKS: usize = 40;
+
+/// Returns the location of the absolute `path` of the system installed below
+/// `root`. Symlinks are followed inside of `root`, so absolute targets do not
+/// lead to the files of the host. Paths of the host system are returned unchanged
+pub fn join(root: &Path, path: &Path) -> PathBuf {
+    if is_host(root) {
+        return path.to_path_buf();
+    }
+    let mut resolved = PathBuf::new();
+    let mut pending = components(path);
+    let mut links = 0;
+    while let Some(component) = pending.pop() {
+        let candidate = resolved.join(&component);
+        match fs::read_link(root.join(&candidate)) {
+            Ok(target) if links < MAX_SYMLINKS => {
+                links += 1;
+                if target.is_absolute() {
+                    resolved.clear();
+                }
+                pending.extend(components(&target));
+            }
+            _ if component == ".." => {
+                let _ = resolved.pop();
+            }
+            _ => resolved = candidate,
+        }
+    }
+    root.join(resolved)
+}
+
+/// Follows the symlinks of `path`, which is already located below `root`, inside of `root`.
+/// Paths of the host system are canonicalized if they exist
+pub fn follow(root: &Path, path: &Path) -> PathBuf {
+    if is_host(root) {
+        return fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    }
+    path.strip_prefix(root).map_or_else(
+        |_| path.to_path_buf(),
+        |path| join(root, &Path::new("/").join(path)),
+    )
+}
+
+/// Returns the absolute path `path` has inside of the system below `root`
+pub fn strip<'b>(root: &Path, path: &'b str) -> &'b str {
+    if is_host(root) {
+        return path;
+    }
+    let root = root.to_string_lossy();
+    path.strip_prefix(root.trim_end_matches('/'))
+        .filter(|path| path.starts_with('/'))
+        .unwrap_or(path)
+}
+
+pub fn is_host(root: &Path) -> bool {
+    root == Path::new("/")
+}
+
+/// Returns the components of `path` in reverse order, so the next one can be popped
+fn components(path: &Path) -> Vec<OsString> {
+    path.components()
+        .rev()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_os_string()),
+            Component::ParentDir => Some(OsString::from("..")),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    /// A root with `/lib` pointing to `/usr/lib`, symlinks trying to escape it
+    /// and a symlink pointing to itself
+    fn create_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("root-test-{}-{}", name, std::process::id()));
+        fs::create_dir_all(root.join("usr/lib")).unwrap();
+        symlink("usr/lib", root.join("lib")).unwrap();
+        symlink("/etc", root.join("usr/lib/absolute")).unwrap();
+        symlink("../../../../../etc", root.join("usr/lib/relative")).unwrap();
+        symlink("loop", root.join("loop")).unwrap();
+        root
+    }
+
+    #[test]
+    fn symlinks_are_followed_inside_of_the_root() {
+        let root = create_root("join");
+        let join = |path: &str| join(&root, Path::new(path));
+        assert_eq!(join("/lib/libfoo.so"), root.join("usr/lib/libfoo.so"));
+        assert_eq!(join("/lib/absolute/passwd"), root.join("etc/passwd"));
+        assert_eq!(join("/lib/relative/passwd"), root.join("etc/passwd"));
+        assert_eq!(join("/usr/../../../etc/passwd"), root.join("etc/passwd"));
+        assert_eq!(join("/loop/libfoo.so"), root.join("loop/libfoo.so"));
+        assert_eq!(
+            follow(&root, &root.join("lib/libfoo.so")),
+            root.join("usr/lib/libfoo.so")
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn paths_of_the_host_are_unchanged() {
+        let path = Path::new("/lib/../usr/lib/libfoo.so");
+        assert_eq!(join(Path::new("/"), path), path);
+        assert_eq!(strip(Path::new("/"), "/usr/lib"), "/usr/lib");
+        assert_eq!(
+            strip(Path::new("/mnt/root/"), "/mnt/root/usr/lib"),
+            "/usr/lib"
+        );
+        assert_eq!(
+            strip(Path::new("/mnt/root"), "/mnt/rootfs/usr"),
+            "/mnt/rootfs/usr"
+        );
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{
+    archive,
+    data::{Error, RuntimeDependency},
+    elf, root,
+};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+const PYTHON_INTERPRETER: &str = "python3";
+const BINARY_DIRECTORY: &str = "/usr/bin";
+const LIBRARY_DIRECTORY: &str = "/usr/lib";
+const PERL_DIRECTORY: &str = "/usr/lib/perl5";
+const RUBY_DIRECTORY: &str = "/usr/lib/ruby";
+const NODE_VERSION_HEADER: &str = "/usr/include/node/node_version.h";
+const NODE_MODULES_DIRECTORY: &str = "/usr/lib/node_modules";
+const MODULE_DIRECTORY: &str = "/usr/lib/modules";
+const KERNEL_RELEASE: &str = "/proc/sys/kernel/osrelease";
+const OCAML_STDLIB_INTERFACE: &str = "/usr/lib/ocaml/stdlib.cmi";
+/// `Caml1999I` followed by the three digits of the format version
+const OCAML_INTERFACE_MAGIC_SIZE: usize = 12;
+const KERNEL_MODULE_EXTENSIONS: &[&str] = &[".ko", ".ko.gz", ".ko.xz", ".ko.zst"];
+/// Files whose version is read from their content
+const COMPILED_EXTENSIONS: &[&str] = &[".node", ".cmi"];
+
+/// A language runtime which only finds the files of packages built for
+/// one of its installed versions
+trait Runtime: fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The version used by default, shown next to the findings
+    fn default_version(&self) -> Option<&str>;
+
+    fn is_installed(&self, version: &str) -> bool;
+
+    /// Returns whether `file`, which was installed for `version`, is used by the runtime
+    fn is_usable(&self, _file: &str, version: &str) -> bool {
+        self.is_installed(version)
+    }
+
+    /// Returns the version `file` was installed for and the location to report
+    /// it by, if the file is specific to a version of the runtime. `file` is the
+    /// path inside of the checked system. The `content` of compiled files is given
+    fn file_version(&self, file: &str, content: Option<&[u8]>) -> Option<(String, String)>;
+}
+
+/// The interpreter versions installed on the system. Files of packages which
+/// were built for another version are not found by the interpreter anymore
+#[derive(Debug)]
+pub struct Runtimes {
+    root: PathBuf,
+    runtimes: Vec<Box<dyn Runtime>>,
+}
+
+/// A runtime which installs files into directories named by its version,
+/// like `/usr/lib/perl5/5.36/vendor_perl`
+#[derive(Debug)]
+struct VersionedDirectories {
+    name: &'static str,
+    default_version: Option<String>,
+    versions: HashSet<String>,
+    /// captures the version. The whole match is the reported location
+    pattern: Regex,
+}
+
+#[derive(Debug)]
+struct Python {
+    directories: VersionedDirectories,
+    /// extension modules tagged with the ABI like `.cpython-311-x86_64-linux-gnu.so`
+    module: Regex,
+}
+
+/// Native addons of node register themselves for a single `NODE_MODULE_VERSION`.
+/// Addons using the stable N-API are not affected
+#[derive(Debug)]
+struct Node {
+    version: Option<String>,
+    register: Regex,
+}
+
+/// Compiled interfaces (`.cmi`) can only be read by the compiler version which
+/// wrote them
+#[derive(Debug)]
+struct Ocaml {
+    version: Option<String>,
+}
+
+/// Kernel modules are only loaded by the kernel release they were built for
+#[derive(Debug)]
+struct Kernel {
+    /// the installed kernels, starting with the running one
+    releases: Vec<String>,
+    installed: Option<String>,
+}
+
+impl Runtimes {
+    /// Detects the interpreters installed below `root`
+    pub fn detect<'a>(root: &Path) -> Result<Self, Error<'a>> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            runtimes: vec![
+                Box::new(Python::detect(root)?),
+                Box::new(perl(root)?),
+                Box::new(ruby(root)?),
+                Box::new(Node::detect(root)?),
+                Box::new(Ocaml::detect(root)),
+                Box::new(Kernel::detect(root)),
+            ],
+        })
+    }
+
+    /// Returns the files of a package which belong to a runtime version
+    /// that is not installed. `read` returns the content of compiled files,
+    /// which are not installed yet for archives
+    pub fn verify_files<F>(&self, files: &[String], mut read: F) -> Vec<RuntimeDependency>
+    where
+        F: FnMut(&str) -> Option<Vec<u8>>,
+    {
+        // the files of a stale directory are reported by the directory they are installed to
+        let mut stale: Vec<BTreeMap<String, Vec<String>>> =
+            self.runtimes.iter().map(|_| BTreeMap::new()).collect();
+        for file in files.iter().filter(|file| !file.ends_with('/')) {
+            let path = root::strip(&self.root, file);
+            let content = if is_compiled(path) { read(file) } else { None };
+            for (runtime, stale) in self.runtimes.iter().zip(stale.iter_mut()) {
+                let Some((version, location)) = runtime.file_version(path, content.as_deref())
+                else {
+                    continue;
+                };
+                // the locations are reported like the files of the package
+                let location = if root::is_host(&self.root) {
+                    location
+                } else {
+                    self.root
+                        .join(location.trim_start_matches('/'))
+                        .to_string_lossy()
+                        .into_owned()
+                };
+                if runtime.is_usable(path, &version) {
+                    continue;
+                }
+                let locations = stale.entry(version).or_default();
+                if !locations.contains(&location) {
+                    locations.push(location);
+                }
+            }
+        }
+        self.runtimes
+            .iter()
+            .zip(stale)
+            .flat_map(|(runtime, stale)| {
+                stale
+                    .into_iter()
+                    .map(move |(version, files)| RuntimeDependency {
+                        runtime: String::from(runtime.name()),
+                        version,
+                        installed_version: runtime.default_version().map(String::from),
+                        files,
+                    })
+            })
+            .collect()
+    }
+}
+
+impl Runtime for VersionedDirectories {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn default_version(&self) -> Option<&str> {
+        self.default_version.as_deref()
+    }
+
+    fn is_installed(&self, version: &str) -> bool {
+        self.versions.contains(version)
+    }
+
+    fn file_version(&self, file: &str, _: Option<&[u8]>) -> Option<(String, String)> {
+        let captures = self.pattern.captures(file)?;
+        Some((
+            String::from(captures.get(1)?.as_str()),
+            String::from(captures.get(0)?.as_str()),
+        ))
+    }
+}
+
+impl Python {
+    #[allow(clippy::indexing_slicing)]
+    fn detect<'a>(root: &Path) -> Result<Self, Error<'a>> {
+        let interpreter = Regex::new(r"^python(\d+\.\d+)$")?;
+        let binaries = root::join(root, Path::new(BINARY_DIRECTORY));
+        let default_version = fs::read_link(binaries.join(PYTHON_INTERPRETER))
+            .ok()
+            .and_then(|target| {
+                let target = target.file_name()?.to_string_lossy().into_owned();
+                interpreter
+                    .captures(&target)
+                    .map(|captures| String::from(&captures[1]))
+            });
+        // interpreters like `python3.11` might be installed next to the default one
+        let versions = directory_names(&binaries)
+            .into_iter()
+            .filter_map(|name| {
+                interpreter
+                    .captures(&name)
+                    .map(|captures| String::from(&captures[1]))
+            })
+            .collect();
+        Ok(Self {
+            directories: VersionedDirectories {
+                name: "python",
+                default_version,
+                versions,
+                // keep the directory the package installed into, like `site-packages/foo`
+                pattern: Regex::new(r"^/usr/lib/python(\d+\.\d+)/[^/]+(?:/[^/]+)?")?,
+            },
+            module: Regex::new(r"^/usr/lib/python[^/]*/.*\.cpython-(\d)(\d+)[^/]*\.so$")?,
+        })
+    }
+}
+
+impl Runtime for Python {
+    fn name(&self) -> &'static str {
+        self.directories.name()
+    }
+
+    fn default_version(&self) -> Option<&str> {
+        self.directories.default_version()
+    }
+
+    fn is_installed(&self, version: &str) -> bool {
+        self.directories.is_installed(version)
+    }
+
+    /// Modules are only imported by the interpreter of their directory
+    fn is_usable(&self, file: &str, version: &str) -> bool {
+        self.is_installed(version)
+            && self
+                .directories
+                .file_version(file, None)
+                .is_none_or(|(directory, _)| directory == version)
+    }
+
+    #[allow(clippy::indexing_slicing)]
+    fn file_version(&self, file: &str, content: Option<&[u8]>) -> Option<(String, String)> {
+        let directory = self.directories.file_version(file, content);
+        let module = self
+            .module
+            .captures(file)
+            .map(|captures| format!("{}.{}", &captures[1], &captures[2]));
+        match module {
+            // a module built for another version than its directory is reported by itself
+            Some(module)
+                if directory
+                    .as_ref()
+                    .is_none_or(|(version, _)| *version != module) =>
+            {
+                Some((module, String::from(file)))
+            }
+            _ => directory,
+        }
+    }
+}
+
+/// Every installed perl has its core modules in `/usr/lib/perl5/X.Y/core_perl`
+fn perl<'a>(root: &Path) -> Result<VersionedDirectories, Error<'a>> {
+    let versions = installed_versions(&root::join(root, Path::new(PERL_DIRECTORY)), "core_perl");
+    Ok(VersionedDirectories {
+        name: "perl",
+        default_version: newest(&versions),
+        versions,
+        pattern: Regex::new(r"^/usr/lib/perl5/(\d+\.\d+)/[^/]+(?:/[^/]+)?")?,
+    })
+}
+
+/// Every installed ruby has its standard library in `/usr/lib/ruby/X.Y.Z`
+fn ruby<'a>(root: &Path) -> Result<VersionedDirectories, Error<'a>> {
+    let versions = installed_versions(&root::join(root, Path::new(RUBY_DIRECTORY)), "rubygems.rb");
+    Ok(VersionedDirectories {
+        name: "ruby",
+        default_version: newest(&versions),
+        versions,
+        pattern: Regex::new(
+            r"^/usr/lib/ruby/(?:gems/|vendor_ruby/|site_ruby/)?(\d+\.\d+\.\d+)/[^/]+(?:/[^/]+)?",
+        )?,
+    })
+}
+
+impl Node {
+    fn detect<'a>(root: &Path) -> Result<Self, Error<'a>> {
+        let definition = Regex::new(r"(?m)^#define NODE_MODULE_VERSION (\d+)")?;
+        let version = fs::read_to_string(root::join(root, Path::new(NODE_VERSION_HEADER)))
+            .ok()
+            .and_then(|header| {
+                definition
+                    .captures(&header)
+                    .and_then(|captures| captures.get(1))
+                    .map(|version| String::from(version.as_str()))
+            });
+        Ok(Self {
+            version,
+            register: Regex::new(r"^node_register_module_v(\d+)$")?,
+        })
+    }
+}
+
+impl Runtime for Node {
+    fn name(&self) -> &'static str {
+        "node module version"
+    }
+
+    fn default_version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    fn is_installed(&self, version: &str) -> bool {
+        self.version.as_deref() == Some(version)
+    }
+
+    fn file_version(&self, file: &str, content: Option<&[u8]>) -> Option<(String, String)> {
+        // addons elsewhere are loaded by bundled runtimes like electron
+        let file_path = Path::new(file);
+        if self.version.is_none()
+            || !file_path.starts_with(NODE_MODULES_DIRECTORY)
+            || file_path
+                .extension()
+                .is_none_or(|extension| extension != "node")
+        {
+            return None;
+        }
+        // unreadable addons are skipped, the elf verification reports broken files
+        let symbols = elf::parse_symbols(content?)?;
+        symbols.defined.iter().find_map(|symbol| {
+            let version = self.register.captures(symbol)?.get(1)?.as_str();
+            Some((String::from(version), String::from(file)))
+        })
+    }
+}
+
+impl Ocaml {
+    fn detect(root: &Path) -> Self {
+        Self {
+            version: fs::read(root::join(root, Path::new(OCAML_STDLIB_INTERFACE)))
+                .ok()
+                .and_then(|content| interface_version(&content)),
+        }
+    }
+}
+
+impl Runtime for Ocaml {
+    fn name(&self) -> &'static str {
+        "ocaml interface format"
+    }
+
+    fn default_version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    fn is_installed(&self, version: &str) -> bool {
+        self.version.as_deref() == Some(version)
+    }
+
+    fn file_version(&self, file: &str, content: Option<&[u8]>) -> Option<(String, String)> {
+        let file = Path::new(file);
+        if !file.starts_with("/usr/lib/ocaml")
+            || file.extension().is_none_or(|extension| extension != "cmi")
+        {
+            return None;
+        }
+        let version = interface_version(content?)?;
+        Some((version, file.parent()?.to_string_lossy().into_owned()))
+    }
+}
+
+impl Kernel {
+    /// Every kernel package installs its image next to its modules as
+    /// `/usr/lib/modules/<release>/vmlinuz`
+    fn detect(root: &Path) -> Self {
+        let modules = root::join(root, Path::new(MODULE_DIRECTORY));
+        let mut releases = directory_names(&modules)
+            .into_iter()
+            .filter(|release| modules.join(release).join("vmlinuz").exists())
+            .collect::<Vec<String>>();
+        releases.sort();
+        // the kernel of another root is not running
+        let running = fs::read_to_string(KERNEL_RELEASE)
+            .ok()
+            .filter(|_| root::is_host(root));
+        if let Some(running) = running.and_then(|release| {
+            releases
+                .iter()
+                .position(|installed| *installed == release.trim())
+        }) {
+            let running = releases.remove(running);
+            releases.insert(0, running);
+        }
+        let installed = if releases.is_empty() {
+            None
+        } else {
+            Some(releases.join(", "))
+        };
+        Self {
+            releases,
+            installed,
+        }
+    }
+}
+
+impl Runtime for Kernel {
+    fn name(&self) -> &'static str {
+        "kernel"
+    }
+
+    fn default_version(&self) -> Option<&str> {
+        self.installed.as_deref()
+    }
+
+    fn is_installed(&self, version: &str) -> bool {
+        self.releases.iter().any(|release| release == version)
+    }
+
+    fn file_version(&self, file: &str, content: Option<&[u8]>) -> Option<(String, String)> {
+        let is_module = KERNEL_MODULE_EXTENSIONS
+            .iter()
+            .any(|extension| file.ends_with(extension));
+        if !Path::new(file).starts_with(MODULE_DIRECTORY) || !is_module {
+            return None;
+        }
+        let module = archive::read_compressed(file, content?).ok()?;
+        Some((elf::parse_module_kernel(&module)?, String::from(file)))
+    }
+}
+
+/// A library built by ghc. Its soname `libHSaeson-2.1.2.1-<hash>-ghc9.4.4.so`
+/// contains a hash which changes whenever the library is rebuilt
+#[derive(Debug)]
+pub struct HaskellLibrary {
+    /// the haskell package and its version, like `aeson-2.1.2.1`
+    pub package: String,
+    pub ghc_version: String,
+}
+
+impl HaskellLibrary {
+    pub fn parse(library: &str) -> Option<Self> {
+        let name = library.strip_prefix("libHS")?.strip_suffix(".so")?;
+        let (package, ghc_version) = name.rsplit_once("-ghc")?;
+        // the hash is left out by some distributions
+        let package = match package.rsplit_once('-') {
+            Some((versioned, hash))
+                if !hash
+                    .chars()
+                    .all(|character| character.is_ascii_digit() || character == '.') =>
+            {
+                versioned
+            }
+            _ => package,
+        };
+        Some(Self {
+            package: String::from(package),
+            ghc_version: String::from(ghc_version),
+        })
+    }
+}
+
+/// Returns the versions of ghc installed into `/usr/lib/ghc-X.Y.Z` below `root`
+pub fn installed_ghc_versions(root: &Path) -> Vec<String> {
+    let mut versions = directory_names(&root::join(root, Path::new(LIBRARY_DIRECTORY)))
+        .into_iter()
+        .filter_map(|name| name.strip_prefix("ghc-").map(String::from))
+        .collect::<Vec<String>>();
+    versions.sort();
+    versions
+}
+
+/// Reads the format version of a compiled ocaml interface
+fn interface_version(content: &[u8]) -> Option<String> {
+    let magic = std::str::from_utf8(content.get(..OCAML_INTERFACE_MAGIC_SIZE)?).ok()?;
+    magic.strip_prefix("Caml1999I").map(String::from)
+}
+
+/// Returns whether the version of `file` is read from its content
+pub fn is_compiled(file: &str) -> bool {
+    KERNEL_MODULE_EXTENSIONS
+        .iter()
+        .chain(COMPILED_EXTENSIONS)
+        .any(|extension| file.ends_with(extension))
+}
+
+fn directory_names(directory: &Path) -> Vec<String> {
+    fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the names of the directories below `directory` containing `marker`
+fn installed_versions(directory: &Path, marker: &str) -> HashSet<String> {
+    directory_names(directory)
+        .into_iter()
+        .filter(|version| directory.join(version).join(marker).exists())
+        .collect()
+}
+
+/// Compares the numbers of dotted versions, so `5.38` is newer than `5.8`
+fn newest(versions: &HashSet<String>) -> Option<String> {
+    versions
+        .iter()
+        .max_by_key(|version| {
+            version
+                .split('.')
+                .map(|number| number.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<u64>>()
+        })
+        .cloned()
+}